@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+use oak_http_server::asynchronous::AsyncServer;
+
+fn main() {
+    let hostname = "localhost";
+    let port: u16 = 2300;
+
+    let mut server = AsyncServer::new(hostname, port);
+
+    server.on_get("/ping", |_request, response| async move {
+        response.send("Pong!");
+    });
+
+    // While this handler waits, the server keeps processing other connections
+    server.on_get("/slow", |_request, response| async move {
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        response.send("Sorry for the wait!");
+    });
+
+    // Build a tokio runtime and run the server on it
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(server.start(|| {
+            println!("Successfully initiated server");
+        }));
+}
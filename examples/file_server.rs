@@ -11,7 +11,7 @@ fn main() {
 
     server.on_directory("/www/same", read_same_dir);
 
-    server.on_directory("/different", read_diff_dir("/www/different"));
+    server.on_directory("/different", read_diff_dir("www/different"));
 
     server.start(|| {
         println!("Started file server");
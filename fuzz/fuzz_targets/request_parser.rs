@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use oak_http_server::{Request, Target, Version};
+
+fuzz_target!(|data: &[u8]| {
+    // Parse the input as a whole request, including its body
+    let _ = Request::from_reader(&mut &data[..]);
+
+    // Also feed it to the parsers of the individual request line components
+    if let Ok(string) = std::str::from_utf8(data) {
+        let _ = Version::new(string);
+        let _ = Target::new(string);
+    }
+});
@@ -0,0 +1,798 @@
+//! An asynchronous variant of the server, built on the [`tokio`](https://docs.rs/tokio) runtime (requires the `async` feature)
+//!
+//! Instead of processing connections on a fixed number of worker threads, an [`AsyncServer`] spawns a task for each connection, so slow clients or handlers don't hold up the others.
+//! Requests are parsed into the same [`Request`] struct as in the synchronous [`Server`](crate::Server), while handlers respond with an [`AsyncResponse`]
+//!
+//! # Example
+//!
+//! ```
+//! use oak_http_server::asynchronous::AsyncServer;
+//!
+//! fn main() {
+//!     let mut server = AsyncServer::new("localhost", 2300 as u16);
+//!
+//!     // Handlers are closures returning a future, so they can await other asynchronous operations before responding
+//!     server.on_get("/ping", |_request, response| async move {
+//!         response.send("Pong!");
+//!     });
+//!
+//!     // The server would then be started from within a tokio runtime:
+//!     // server.start(|| println!("Successfully initiated server")).await;
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::process::exit;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+
+use crate::utils::*;
+use crate::{
+    allowed_methods, find_handler, format_time, Cookie, HandlerMethod, Method, Request, Status,
+    Timeouts, Version, DEFAULT_MAX_BODY_SIZE, DEFAULT_MAX_HEADERS, DEFAULT_MAX_HEADER_SIZE,
+    VERSION,
+};
+
+/// The future returned by an [`AsyncHandlerCallback`]
+pub type HandlerFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// The type of the callback function of an [`AsyncHandler`]
+///
+/// The callback should always send the [`AsyncResponse`] it is given. If it is dropped without being sent, the server responds with a `500 Internal Server Error` status instead.
+/// The same status is sent if the future panics before responding, after which the connection is closed
+pub type AsyncHandlerCallback = dyn Fn(Request, AsyncResponse) -> HandlerFuture + Send + Sync;
+
+/// The type of an asynchronous request handler
+pub type AsyncHandler = (HandlerMethod, Box<AsyncHandlerCallback>);
+
+/// An asynchronous HTTP server, which processes each connection in a separate task
+///
+/// It mirrors the API of the synchronous [`Server`](crate::Server), but its handlers return futures. Apart from [timeouts](Self::timeouts) and the limits on the size of requests, the configuration options of the synchronous server aren't available yet
+pub struct AsyncServer {
+    /// The hostname the server is listening to for requests
+    pub hostname: String,
+    /// The port the server is listening for requests
+    pub port: u16,
+
+    handlers: HashMap<String, Vec<AsyncHandler>>,
+
+    timeouts: Timeouts,
+    max_body_size: usize,
+    max_headers: usize,
+    max_header_size: usize,
+}
+
+impl AsyncServer {
+    /// Initialize an [`AsyncServer`] by passing a hostname and a port number
+    pub fn new<S, N>(hostname: S, port: N) -> Self
+    where
+        S: Into<String>,
+        N: Into<u16>,
+    {
+        Self {
+            hostname: hostname.into(),
+            port: port.into(),
+
+            handlers: HashMap::new(),
+
+            timeouts: Timeouts::default(),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            max_headers: DEFAULT_MAX_HEADERS,
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+        }
+    }
+
+    /// Set the time limits the server enforces on each phase of a connection. Check the [`Timeouts`] documentation for their default values
+    pub fn timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Set the maximum size of a request's message body in bytes (2 MiB by default). Check [`Server::max_body_size()`](crate::Server::max_body_size()) for details
+    pub fn max_body_size(&mut self, max_body_size: usize) {
+        self.max_body_size = max_body_size;
+    }
+
+    /// Set the maximum number of header fields a request may contain (100 by default). Check [`Server::max_headers()`](crate::Server::max_headers()) for details
+    pub fn max_headers(&mut self, max_headers: usize) {
+        self.max_headers = max_headers;
+    }
+
+    /// Set the maximum total size in bytes of the header fields of a request (8 KiB by default). Check [`Server::max_header_size()`](crate::Server::max_header_size()) for details
+    pub fn max_header_size(&mut self, max_header_size: usize) {
+        self.max_header_size = max_header_size;
+    }
+
+    /// Start the server and make it process incoming connections. This must be awaited from within a tokio runtime
+    pub async fn start(self, callback: fn()) {
+        // Initiate a TCP Listener at the server's hostname and port
+        let listener = TcpListener::bind((unbracket_host(&self.hostname), self.port))
+            .await
+            .unwrap_or_else(|err| {
+                eprintln!("Couldn't initiate TCP server. Error message: {}", err);
+                exit(1);
+            });
+
+        callback();
+
+        // Share the server between the connection tasks
+        let server = Arc::new(self);
+
+        // For each incoming connection request, accept connection and process it in a new task
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let server = Arc::clone(&server);
+                    tokio::spawn(async move { server.handle_connection(stream).await });
+                }
+                Err(e) => {
+                    eprintln!("Failed to establish a new connection. Error message: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Append a function handler that will be called on any request in a specific path
+    pub fn on<S, H, F>(&mut self, path: S, handler: H)
+    where
+        S: Into<String>,
+        H: Fn(Request, AsyncResponse) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.append_handler(path.into(), HandlerMethod::Any, handler);
+    }
+
+    /// Same as the [`on`](Self::on) function, but processes only GET requests
+    pub fn on_get<S, H, F>(&mut self, path: S, handler: H)
+    where
+        S: Into<String>,
+        H: Fn(Request, AsyncResponse) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.append_handler(path.into(), HandlerMethod::Specific(Method::GET), handler);
+    }
+
+    /// Same as the [`on`](Self::on) function, but processes only HEAD requests
+    pub fn on_head<S, H, F>(&mut self, path: S, handler: H)
+    where
+        S: Into<String>,
+        H: Fn(Request, AsyncResponse) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.append_handler(path.into(), HandlerMethod::Specific(Method::HEAD), handler);
+    }
+
+    /// Same as the [`on`](Self::on) function, but processes only POST requests
+    pub fn on_post<S, H, F>(&mut self, path: S, handler: H)
+    where
+        S: Into<String>,
+        H: Fn(Request, AsyncResponse) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.append_handler(path.into(), HandlerMethod::Specific(Method::POST), handler);
+    }
+
+    /// Same as the [`on`](Self::on) function, but processes only PUT requests
+    pub fn on_put<S, H, F>(&mut self, path: S, handler: H)
+    where
+        S: Into<String>,
+        H: Fn(Request, AsyncResponse) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.append_handler(path.into(), HandlerMethod::Specific(Method::PUT), handler);
+    }
+
+    /// Same as the [`on`](Self::on) function, but processes only DELETE requests
+    pub fn on_delete<S, H, F>(&mut self, path: S, handler: H)
+    where
+        S: Into<String>,
+        H: Fn(Request, AsyncResponse) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.append_handler(
+            path.into(),
+            HandlerMethod::Specific(Method::DELETE),
+            handler,
+        );
+    }
+
+    /// Same as the [`on`](Self::on) function, but processes only PATCH requests
+    pub fn on_patch<S, H, F>(&mut self, path: S, handler: H)
+    where
+        S: Into<String>,
+        H: Fn(Request, AsyncResponse) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.append_handler(path.into(), HandlerMethod::Specific(Method::PATCH), handler);
+    }
+
+    /// Same as the [`on`](Self::on) function, but processes only OPTIONS requests
+    pub fn on_options<S, H, F>(&mut self, path: S, handler: H)
+    where
+        S: Into<String>,
+        H: Fn(Request, AsyncResponse) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.append_handler(
+            path.into(),
+            HandlerMethod::Specific(Method::OPTIONS),
+            handler,
+        );
+    }
+
+    /// Same as the [`on`](Self::on) function, but processes only requests with the given [`Method`]
+    pub fn on_method<S, H, F>(&mut self, path: S, method: Method, handler: H)
+    where
+        S: Into<String>,
+        H: Fn(Request, AsyncResponse) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.append_handler(path.into(), HandlerMethod::Specific(method), handler);
+    }
+
+    /// Append a directory handler that will be called on any request in a specific path
+    pub fn on_directory<S, H, F>(&mut self, path: S, handler: H)
+    where
+        S: Into<String>,
+        H: Fn(Request, AsyncResponse) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.append_handler(path.into(), HandlerMethod::Directory, handler);
+    }
+
+    fn append_handler<H, F>(&mut self, path: String, method: HandlerMethod, handler: H)
+    where
+        H: Fn(Request, AsyncResponse) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let handler: Box<AsyncHandlerCallback> =
+            Box::new(move |request, response| Box::pin(handler(request, response)));
+
+        self.handlers
+            .entry(path)
+            .or_default()
+            .push((method, handler));
+    }
+
+    async fn handle_connection(&self, stream: TcpStream) {
+        let mut stream = BufReader::new(stream);
+        let write_timeout = self.timeouts.write;
+
+        loop {
+            // Wait for the request to begin. If the client stays idle for too long or closes the connection, there is nothing to respond to
+            match with_timeout(self.timeouts.keep_alive_idle, stream.fill_buf()).await {
+                Some(Ok(buffer)) if !buffer.is_empty() => (),
+                _ => break,
+            }
+
+            let mut request = match read_request(
+                &mut stream,
+                &self.timeouts,
+                self.max_body_size,
+                self.max_headers,
+                self.max_header_size,
+            )
+            .await
+            {
+                Ok(request) => request,
+                Err(status) => {
+                    // Respond with an error status, unless the connection itself failed
+                    if let Some(status) = status {
+                        respond_with_status(&mut stream, status, write_timeout).await;
+                    }
+                    break;
+                }
+            };
+
+            request.peer_address = stream.get_ref().peer_addr().ok();
+
+            // Before responding, check if the HTTP version of the request is supported (HTTP/1.0 or HTTP/1.1). The response is sent in the same version
+            let Some(version) = response_version(&request.version) else {
+                eprintln!(
+                    "Expected HTTP version HTTP/1.0 or {}, found {}. Dropping connection...",
+                    VERSION, request.version
+                );
+                respond_with_status(&mut stream, Status::BadRequest, write_timeout).await;
+                break;
+            };
+
+            // Then check if a `Host` was sent, else respond with a 400 status code. It is only required since HTTP/1.1
+            if get_header(&request.headers, "Host").is_none()
+                && version == Version::new(VERSION).unwrap()
+            {
+                eprintln!("Expected 'Host' header, found nothing. Dropping connection...");
+                respond_with_status(&mut stream, Status::BadRequest, write_timeout).await;
+                break;
+            }
+
+            let mut close = !request.is_keep_alive();
+
+            // The response is passed back from the handler through a channel, so that it can be written to the connection afterwards
+            let (sender, mut receiver) = oneshot::channel();
+            let head_request = request.method == Method::HEAD;
+            let mut response = AsyncResponse::new(sender, &version, close, head_request);
+            #[cfg(feature = "compression")]
+            {
+                response.accepted_encoding = negotiate_encoding(&request.headers);
+            }
+
+            let mut timed_out = false;
+            let mut panicked = false;
+            match find_handler(&self.handlers, &mut request) {
+                Some(handler) => {
+                    // The handler runs in its own task, so that a panic in it can be caught
+                    let task = tokio::spawn(handler(request, response));
+                    let abort_handle = task.abort_handle();
+
+                    match with_timeout(self.timeouts.handler, task).await {
+                        None => {
+                            abort_handle.abort();
+                            timed_out = true;
+                        }
+                        Some(Err(error)) if error.is_panic() => {
+                            eprintln!(
+                                "Handler panicked with message '{}'. Dropping connection...",
+                                panic_message(error.into_panic().as_ref())
+                            );
+                            panicked = true;
+                            close = true;
+                        }
+                        Some(_) => (),
+                    }
+                }
+                None => match allowed_methods(&self.handlers, &request) {
+                    Some(allow) => {
+                        response.status(Status::MethodNotAllowed);
+                        response.headers.insert(String::from("Allow"), allow);
+                        response.end();
+                    }
+                    None => {
+                        response.status(Status::NotFound);
+                        response.end();
+                    }
+                },
+            }
+
+            // A handler that timed out may still be running until it reaches an await point, so it isn't waited for
+            let received = if timed_out {
+                receiver.try_recv().ok()
+            } else {
+                receiver.await.ok()
+            };
+
+            let bytes = match received {
+                Some(bytes) => bytes,
+                None => {
+                    let status = if timed_out {
+                        eprintln!("Handler timed out. Responding with a 503 status code...");
+                        close = true;
+                        Status::ServiceUnavailable
+                    } else {
+                        // A handler that drops its response without sending it would leave the client waiting forever
+                        if !panicked {
+                            eprintln!("Handler returned without sending a response. Responding with a 500 status code...");
+                        }
+                        Status::InternalError
+                    };
+
+                    let (sender, receiver) = oneshot::channel();
+                    let mut response = AsyncResponse::new(sender, &version, close, head_request);
+                    response.status(status);
+                    response.end();
+                    receiver.await.unwrap()
+                }
+            };
+
+            let written = with_timeout(write_timeout, stream.get_mut().write_all(&bytes)).await;
+            if !matches!(written, Some(Ok(()))) || close {
+                break;
+            }
+        }
+
+        let _ = stream.get_mut().shutdown().await;
+    }
+}
+
+/// A HTTP response for the [`AsyncServer`] to reply to the client
+///
+/// Unlike a synchronous [`Response`](crate::Response), it doesn't write to the connection directly; instead, the server sends it once the handler has finished
+pub struct AsyncResponse {
+    sender: oneshot::Sender<Vec<u8>>,
+
+    /// The HTTP status code of the response
+    pub status: Status,
+    status_text: Option<String>,
+    /// The HTTP version of the response
+    pub version: Version,
+
+    /// A type alias of a Hashmap containing the headers of the response
+    pub headers: Headers,
+
+    cookies: HashMap<String, Cookie>,
+    head_request: bool,
+    #[cfg(feature = "compression")]
+    accepted_encoding: Option<&'static str>,
+}
+
+impl AsyncResponse {
+    fn new(
+        sender: oneshot::Sender<Vec<u8>>,
+        version: &Version,
+        close: bool,
+        head_request: bool,
+    ) -> Self {
+        let mut headers = Headers::new();
+        headers.insert(String::from("Date"), format_time(SystemTime::now()));
+
+        // Let the client know that the server will close the connection after this response.
+        // HTTP/1.0 clients expect the connection to be closed unless told otherwise
+        if close {
+            headers.insert(String::from("Connection"), String::from("close"));
+        } else if *version != Version::new(VERSION).unwrap() {
+            headers.insert(String::from("Connection"), String::from("keep-alive"));
+        }
+
+        Self {
+            sender,
+
+            status: Status::OK,
+            status_text: None,
+            version: version.clone(),
+
+            headers,
+
+            cookies: HashMap::new(),
+            head_request,
+            #[cfg(feature = "compression")]
+            accepted_encoding: None,
+        }
+    }
+
+    /// Change the [`Status`] of the response
+    pub fn status(&mut self, status: Status) {
+        self.status = status;
+    }
+
+    /// Set the reason phrase sent after the status code in the status line. Check [`Response::status_text()`](crate::Response::status_text()) for details
+    pub fn status_text<S>(&mut self, text: S)
+    where
+        S: Into<String>,
+    {
+        self.status_text = Some(reason_phrase(text.into()));
+    }
+
+    /// Remove a header from the response, ignoring the case of its name, and return its value (if it had been set)
+    pub fn remove_header(&mut self, name: &str) -> Option<String> {
+        let mut removed = None;
+
+        self.headers.retain(|header_name, value| {
+            if header_name.eq_ignore_ascii_case(name) {
+                removed = Some(value.clone());
+                false
+            } else {
+                true
+            }
+        });
+
+        removed
+    }
+
+    /// Add a [`Cookie`] to the response, replacing any previously set cookie with the same name
+    pub fn set_cookie(&mut self, cookie: Cookie) {
+        self.cookies.insert(cookie.name.clone(), cookie);
+    }
+
+    /// Send the message of the response uncompressed. Check [`Response::disable_compression()`](crate::Response::disable_compression()) for details
+    #[cfg(feature = "compression")]
+    pub fn disable_compression(&mut self) {
+        self.accepted_encoding = None;
+    }
+
+    /// Send the response along with a message (consumes the response)
+    ///
+    /// The message is written in one piece, along with a `Content-Length` header. Any `Content-Length` or `Transfer-Encoding` header set on the response is ignored.
+    /// If the request's method is HEAD, only the head of the response is sent
+    pub fn send<S>(self, message: S)
+    where
+        S: Into<String>,
+    {
+        self.send_bytes(message.into().into_bytes());
+    }
+
+    /// Same as [`send()`](AsyncResponse::send()), but with a message of raw bytes, such as an image or any other binary data (consumes the response)
+    #[cfg_attr(not(feature = "compression"), allow(unused_mut))]
+    pub fn send_bytes<B>(mut self, message: B)
+    where
+        B: Into<Vec<u8>>,
+    {
+        #[cfg_attr(not(feature = "compression"), allow(unused_mut))]
+        let mut message = message.into();
+
+        #[cfg(feature = "compression")]
+        if let Some(encoding) = self.accepted_encoding {
+            message = compress_body(&mut self.headers, message, encoding);
+        }
+
+        let mut bytes = format_head(
+            &self.version,
+            &self.status,
+            self.status_text.as_deref().unwrap_or_default(),
+            Some(message.len() as u64),
+            &self.headers,
+            self.cookies.values(),
+            false,
+        )
+        .into_bytes();
+        if !self.head_request {
+            bytes.extend(message);
+        }
+
+        // If the connection task is gone, there is no one left to send the response to
+        let _ = self.sender.send(bytes);
+    }
+
+    /// Send an empty response (consumes it)
+    pub fn end(self) {
+        self.send("");
+    }
+}
+
+/// Run a future to completion within an optional time limit, returning [`None`] if it expires
+async fn with_timeout<F>(limit: Option<Duration>, future: F) -> Option<F::Output>
+where
+    F: Future,
+{
+    match limit {
+        Some(limit) => tokio::time::timeout(limit, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// Respond with an empty response of the given status and close the connection
+async fn respond_with_status(
+    stream: &mut BufReader<TcpStream>,
+    status: Status,
+    write_timeout: Option<Duration>,
+) {
+    let (sender, receiver) = oneshot::channel();
+    let mut response = AsyncResponse::new(sender, &Version::new(VERSION).unwrap(), true, false);
+    response.status(status);
+    response.end();
+
+    if let Ok(bytes) = receiver.await {
+        let _ = with_timeout(write_timeout, stream.get_mut().write_all(&bytes)).await;
+    }
+}
+
+/// Read a [`Request`] from a connection, including its body
+///
+/// On failure, returns the status that should be sent to the client, or [`None`] if the stream ended or can't be read from
+async fn read_request(
+    stream: &mut BufReader<TcpStream>,
+    timeouts: &Timeouts,
+    max_body_size: usize,
+    max_headers: usize,
+    max_header_size: usize,
+) -> Result<Request, Option<Status>> {
+    // Collect the request line and the header fields within the header read timeout
+    let head = with_timeout(
+        timeouts.header_read,
+        read_head(stream, max_headers, max_header_size),
+    )
+    .await
+    .ok_or_else(|| {
+        eprintln!("Timed out while reading the HTTP request headers. Dropping connection...");
+        Some(Status::RequestTimeout)
+    })??;
+
+    // Then parse them the same way the synchronous server does. The limits have already been enforced
+    let mut request = Request::read_head(&mut &head[..], usize::MAX, usize::MAX)?;
+
+    // Reject a body that is too large before the client is told to send it
+    if declared_length_exceeds(&request.headers, max_body_size) {
+        eprintln!("HTTP message body is too large. Dropping connection...");
+        return Err(Some(Status::ContentTooLarge));
+    }
+
+    // A client that sends `Expect: 100-continue` waits for an interim response before sending the body, so send one before reading it
+    if let Some(expectation) = get_header(&request.headers, "Expect") {
+        if expectation.eq_ignore_ascii_case("100-continue") {
+            stream
+                .get_mut()
+                .write_all(format!("{} 100 Continue\r\n\r\n", VERSION).as_bytes())
+                .await
+                .map_err(|_| None)?;
+        } else {
+            eprintln!("Unsupported expectation detected. Dropping connection...");
+            return Err(Some(Status::ExpectationFailed));
+        }
+    }
+
+    // Read the message body (if any) within the body read timeout, so that the next request on this connection starts right after it
+    let body = with_timeout(
+        timeouts.body_read,
+        read_body(
+            stream,
+            &request.headers,
+            max_body_size,
+            max_headers,
+            max_header_size,
+        ),
+    )
+    .await
+    .ok_or_else(|| {
+        eprintln!("Timed out while reading the HTTP message body. Dropping connection...");
+        Some(Status::RequestTimeout)
+    })?;
+
+    (request.body, request.trailers) = body.map_err(|status| {
+        if status == Status::ContentTooLarge {
+            eprintln!("HTTP message body is too large. Dropping connection...");
+        } else if status == Status::RequestHeaderFieldsTooLarge {
+            eprintln!("Too many or too large HTTP trailer fields detected. Dropping connection...");
+        } else {
+            eprintln!("Invalid HTTP message body detected. Dropping connection...");
+        }
+        Some(status)
+    })?;
+
+    Ok(request)
+}
+
+/// Read the request line and the header fields of a request, up to and including the empty line that ends them
+///
+/// Stops reading with a `431 Request Header Fields Too Large` status once the header fields exceed the given limits
+async fn read_head<R: AsyncBufRead + Unpin>(
+    stream: &mut R,
+    max_headers: usize,
+    max_header_size: usize,
+) -> Result<Vec<u8>, Option<Status>> {
+    let mut head = read_line(stream).await.ok_or(None)?;
+    head.extend(b"\r\n");
+
+    let mut header_count = 0;
+    let mut header_size = 0;
+    loop {
+        let line = read_line(stream).await.ok_or(None)?;
+
+        head.extend(&line);
+        head.extend(b"\r\n");
+
+        if line.is_empty() {
+            return Ok(head);
+        }
+
+        // Don't let the client exhaust the server's memory with a flood of headers
+        header_count += 1;
+        header_size += line.len();
+        if header_count > max_headers || header_size > max_header_size {
+            eprintln!("Too many or too large HTTP headers detected. Dropping connection...");
+            return Err(Some(Status::RequestHeaderFieldsTooLarge));
+        }
+    }
+}
+
+/// Read the message body of a request with the given headers, failing in the same way as the synchronous equivalent
+async fn read_body<R: AsyncBufRead + Unpin>(
+    stream: &mut R,
+    headers: &Headers,
+    max_size: usize,
+    max_trailers: usize,
+    max_trailer_size: usize,
+) -> Result<(Vec<u8>, Headers), Status> {
+    if get_header(headers, "Transfer-Encoding").is_some_and(|encoding| is_chunked(encoding)) {
+        read_chunked_body(stream, max_size, max_trailers, max_trailer_size).await
+    } else if let Some(length) = get_header(headers, "Content-Length") {
+        let length = length.parse::<usize>().map_err(|_| Status::BadRequest)?;
+        if length > max_size {
+            return Err(Status::ContentTooLarge);
+        }
+
+        let body = read_bytes(stream, length).await.ok_or(Status::BadRequest)?;
+        Ok((body, Headers::new()))
+    } else {
+        Ok((Vec::new(), Headers::new()))
+    }
+}
+
+/// Read a line terminated by a CRLF, without the CRLF, returning [`None`] if the stream ends, can't be read from or the line is too long
+async fn read_line<R: AsyncBufRead + Unpin>(stream: &mut R) -> Option<Vec<u8>> {
+    let mut line = Vec::new();
+
+    // Don't let the client exhaust the server's memory with a never-ending line
+    stream
+        .take(MAX_LINE_LENGTH as u64 + 2)
+        .read_until(b'\n', &mut line)
+        .await
+        .ok()?;
+
+    line.strip_suffix(b"\r\n").map(|line| line.to_vec())
+}
+
+/// Read exactly `length` bytes from a stream, returning [`None`] if the stream ends prematurely
+async fn read_bytes<R: AsyncBufRead + Unpin>(stream: &mut R, length: usize) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    // Don't trust the length to preallocate the buffer, since it is usually provided by the client
+    stream
+        .take(length as u64)
+        .read_to_end(&mut bytes)
+        .await
+        .ok()
+        .filter(|&read| read == length)?;
+
+    Some(bytes)
+}
+
+/// Read a message body encoded with the chunked transfer coding (RFC 9112 §7.1), failing in the same way as [`read_body`]
+async fn read_chunked_body<R: AsyncBufRead + Unpin>(
+    stream: &mut R,
+    max_size: usize,
+    max_trailers: usize,
+    max_trailer_size: usize,
+) -> Result<(Vec<u8>, Headers), Status> {
+    let mut body = Vec::new();
+
+    loop {
+        // Each chunk begins with its size in hexadecimal, optionally followed by chunk extensions (which are ignored)
+        let size_line = read_line(stream)
+            .await
+            .and_then(|line| String::from_utf8(line).ok())
+            .ok_or(Status::BadRequest)?;
+        let size_string = size_line.split(';').next().unwrap_or_default().trim();
+        if size_string.is_empty() || !size_string.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(Status::BadRequest);
+        }
+        let size = usize::from_str_radix(size_string, 16).map_err(|_| Status::BadRequest)?;
+
+        // A chunk of zero size signals the end of the body
+        if size == 0 {
+            break;
+        }
+
+        // Stop before reading a chunk that would make the body too large
+        if size > max_size - body.len() {
+            return Err(Status::ContentTooLarge);
+        }
+
+        body.extend(read_bytes(stream, size).await.ok_or(Status::BadRequest)?);
+
+        // The chunk's data must be followed by a CRLF
+        if !read_line(stream)
+            .await
+            .ok_or(Status::BadRequest)?
+            .is_empty()
+        {
+            return Err(Status::BadRequest);
+        }
+    }
+
+    // Then come the trailer fields (if any), up to the final empty line
+    let mut trailers = Headers::new();
+    let mut trailer_count = 0;
+    let mut trailer_size = 0;
+    loop {
+        let line = read_line(stream)
+            .await
+            .and_then(|line| String::from_utf8(line).ok())
+            .ok_or(Status::BadRequest)?;
+
+        if line.is_empty() {
+            return Ok((body, trailers));
+        }
+
+        // Trailer fields are subject to the same limits as header fields
+        trailer_count += 1;
+        trailer_size += line.len();
+        if trailer_count > max_trailers || trailer_size > max_trailer_size {
+            return Err(Status::RequestHeaderFieldsTooLarge);
+        }
+
+        parse_header_line(&mut trailers, line).ok_or(Status::BadRequest)?;
+    }
+}
@@ -1,9 +1,12 @@
-use std::fmt;
+use std::{fmt, io, str::FromStr};
 
 /// A HTTP status to include in a [`Response`](crate::Response)
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Clone, Copy, Debug)]
 #[non_exhaustive]
 pub enum Status {
+    /// `101 Switching Protocols`
+    SwitchingProtocols,
+
     /// `200 OK`
     OK,
     /// `201 Created`
@@ -12,16 +15,55 @@ pub enum Status {
     Accepted,
     /// `203 No Content`
     NoContent,
+    /// `206 Partial Content`
+    PartialContent,
+
+    /// `301 Moved Permanently`
+    MovedPermanently,
+    /// `302 Found`
+    Found,
+    /// `303 See Other`
+    SeeOther,
+    /// `304 Not Modified`
+    NotModified,
+    /// `307 Temporary Redirect`
+    TemporaryRedirect,
+    /// `308 Permanent Redirect`
+    PermanentRedirect,
 
     /// `400 Bad Request`
     BadRequest,
+    /// `401 Unauthorized`
+    Unauthorized,
+    /// `403 Forbidden`
+    Forbidden,
     /// `404 Not Found`
     NotFound,
+    /// `405 Method Not Allowed`
+    MethodNotAllowed,
+    /// `408 Request Timeout`
+    RequestTimeout,
+    /// `413 Content Too Large`
+    ContentTooLarge,
+    /// `415 Unsupported Media Type`
+    UnsupportedMediaType,
+    /// `416 Range Not Satisfiable`
+    RangeNotSatisfiable,
+    /// `417 Expectation Failed`
+    ExpectationFailed,
+    /// `421 Misdirected Request`
+    MisdirectedRequest,
+    /// `422 Unprocessable Content`
+    UnprocessableContent,
+    /// `431 Request Header Fields Too Large`
+    RequestHeaderFieldsTooLarge,
 
     /// `500 Internal Server Error`
     InternalError,
     /// `501 Not Implemented`
     NotImplemented,
+    /// `503 Service Unavailable`
+    ServiceUnavailable,
 }
 
 impl Status {
@@ -37,50 +79,217 @@ impl Status {
     /// # use oak_http_server::Status;
     ///
     /// fn main() {
-    /// 	// Generate a new HTTP Status instance (in our case, Status::OK)
-    /// 	let status: Option<Status> = Status::new(200);
+    ///     // Generate a new HTTP Status instance (in our case, Status::OK)
+    ///     let status: Option<Status> = Status::new(200);
     ///
-    /// 	assert_eq!(status, Some(Status::OK));
+    ///     assert_eq!(status, Some(Status::OK));
     /// }
     /// ```
     pub fn new(status: usize) -> Option<Self> {
         match status {
+            101 => Some(Self::SwitchingProtocols),
+
             200 => Some(Self::OK),
             201 => Some(Self::Created),
             202 => Some(Self::Accepted),
             204 => Some(Self::NoContent),
+            206 => Some(Self::PartialContent),
+
+            301 => Some(Self::MovedPermanently),
+            302 => Some(Self::Found),
+            303 => Some(Self::SeeOther),
+            304 => Some(Self::NotModified),
+            307 => Some(Self::TemporaryRedirect),
+            308 => Some(Self::PermanentRedirect),
 
             400 => Some(Self::BadRequest),
+            401 => Some(Self::Unauthorized),
+            403 => Some(Self::Forbidden),
             404 => Some(Self::NotFound),
+            405 => Some(Self::MethodNotAllowed),
+            408 => Some(Self::RequestTimeout),
+            413 => Some(Self::ContentTooLarge),
+            415 => Some(Self::UnsupportedMediaType),
+            416 => Some(Self::RangeNotSatisfiable),
+            417 => Some(Self::ExpectationFailed),
+            421 => Some(Self::MisdirectedRequest),
+            422 => Some(Self::UnprocessableContent),
+            431 => Some(Self::RequestHeaderFieldsTooLarge),
 
             500 => Some(Self::InternalError),
             501 => Some(Self::NotImplemented),
+            503 => Some(Self::ServiceUnavailable),
             _ => None,
         }
     }
+
+    /// Returns the numeric code of the status (e.g. `404` for [`Status::NotFound`])
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::SwitchingProtocols => 101,
+
+            Self::OK => 200,
+            Self::Created => 201,
+            Self::Accepted => 202,
+            Self::NoContent => 204,
+            Self::PartialContent => 206,
+
+            Self::MovedPermanently => 301,
+            Self::Found => 302,
+            Self::SeeOther => 303,
+            Self::NotModified => 304,
+            Self::TemporaryRedirect => 307,
+            Self::PermanentRedirect => 308,
+
+            Self::BadRequest => 400,
+            Self::Unauthorized => 401,
+            Self::Forbidden => 403,
+            Self::NotFound => 404,
+            Self::MethodNotAllowed => 405,
+            Self::RequestTimeout => 408,
+            Self::ContentTooLarge => 413,
+            Self::UnsupportedMediaType => 415,
+            Self::RangeNotSatisfiable => 416,
+            Self::ExpectationFailed => 417,
+            Self::MisdirectedRequest => 421,
+            Self::UnprocessableContent => 422,
+            Self::RequestHeaderFieldsTooLarge => 431,
+
+            Self::InternalError => 500,
+            Self::NotImplemented => 501,
+            Self::ServiceUnavailable => 503,
+        }
+    }
+
+    /// Returns the class of the status, which is determined by the first digit of its code
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use oak_http_server::{Status, StatusClass};
+    ///
+    /// fn main() {
+    ///     assert_eq!(Status::NotFound.class(), StatusClass::ClientError);
+    ///     assert!(Status::NotFound.is_client_error());
+    /// }
+    /// ```
+    pub fn class(&self) -> StatusClass {
+        match self.code() {
+            100..=199 => StatusClass::Informational,
+            200..=299 => StatusClass::Success,
+            300..=399 => StatusClass::Redirection,
+            400..=499 => StatusClass::ClientError,
+            _ => StatusClass::ServerError,
+        }
+    }
+
+    /// Returns whether the status is informational (`1xx`)
+    pub fn is_informational(&self) -> bool {
+        self.class() == StatusClass::Informational
+    }
+
+    /// Returns whether the status indicates success (`2xx`)
+    pub fn is_success(&self) -> bool {
+        self.class() == StatusClass::Success
+    }
+
+    /// Returns whether the status is a redirection (`3xx`)
+    pub fn is_redirection(&self) -> bool {
+        self.class() == StatusClass::Redirection
+    }
+
+    /// Returns whether the status indicates a client error (`4xx`)
+    pub fn is_client_error(&self) -> bool {
+        self.class() == StatusClass::ClientError
+    }
+
+    /// Returns whether the status indicates a server error (`5xx`)
+    pub fn is_server_error(&self) -> bool {
+        self.class() == StatusClass::ServerError
+    }
 }
 
 impl fmt::Display for Status {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Self::OK => 200,
-                Self::Created => 201,
-                Self::Accepted => 202,
-                Self::NoContent => 204,
+        write!(f, "{}", self.code())
+    }
+}
 
-                Self::BadRequest => 400,
-                Self::NotFound => 404,
+impl TryFrom<u16> for Status {
+    type Error = ParseStatusError;
 
-                Self::InternalError => 500,
-                Self::NotImplemented => 501,
-            }
-        )
+    fn try_from(code: u16) -> Result<Self, Self::Error> {
+        Self::new(code as usize).ok_or(ParseStatusError::Unsupported(code))
     }
 }
 
+impl FromStr for Status {
+    type Err = ParseStatusError;
+
+    /// Parse a status from its numeric code, such as the one in the status line of a response
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use oak_http_server::{ParseStatusError, Status};
+    ///
+    /// fn main() {
+    ///     assert_eq!("404".parse::<Status>(), Ok(Status::NotFound));
+    ///     assert_eq!("4o4".parse::<Status>(), Err(ParseStatusError::Invalid));
+    /// }
+    /// ```
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        if code.len() != 3 || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(ParseStatusError::Invalid);
+        }
+
+        Self::try_from(code.parse::<u16>().map_err(|_| ParseStatusError::Invalid)?)
+    }
+}
+
+impl TryFrom<&str> for Status {
+    type Error = ParseStatusError;
+
+    fn try_from(code: &str) -> Result<Self, Self::Error> {
+        code.parse()
+    }
+}
+
+/// The reason a [`Status`] couldn't be created from a code
+#[derive(PartialEq, Clone, Debug)]
+pub enum ParseStatusError {
+    /// The code isn't a three-digit number
+    Invalid,
+    /// The code is valid, but there is no [`Status`] variant for it
+    Unsupported(u16),
+}
+
+impl fmt::Display for ParseStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Invalid => write!(f, "the status code isn't a three-digit number"),
+            Self::Unsupported(code) => write!(f, "the status code {} isn't supported", code),
+        }
+    }
+}
+
+impl std::error::Error for ParseStatusError {}
+
+/// The class of a [`Status`], as returned by [`Status::class()`]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum StatusClass {
+    /// `1xx`: the request was received and is being processed
+    Informational,
+    /// `2xx`: the request was successfully received, understood and accepted
+    Success,
+    /// `3xx`: further action needs to be taken to complete the request
+    Redirection,
+    /// `4xx`: the request contains bad syntax or can't be fulfilled
+    ClientError,
+    /// `5xx`: the server failed to fulfill an apparently valid request
+    ServerError,
+}
+
 /// A HTTP method that is provided by the client
 #[derive(PartialEq, Clone, Debug)]
 #[non_exhaustive]
@@ -96,6 +305,14 @@ pub enum Method {
     PUT,
     /// The `DELETE` method deletes the specified resource.
     DELETE,
+    /// The `PATCH` method applies partial modifications to a resource.
+    PATCH,
+    /// The `OPTIONS` method requests the communication options available for the target resource.
+    OPTIONS,
+    /// The `TRACE` method performs a message loop-back test along the path to the target resource.
+    TRACE,
+    /// The `CONNECT` method establishes a tunnel to the server identified by the target resource.
+    CONNECT,
 }
 
 impl Method {
@@ -111,10 +328,10 @@ impl Method {
     /// # use oak_http_server::Method;
     ///
     /// fn main() {
-    /// 	// Create a new HTTP Method instance (in our case, Method::GET)
-    /// 	let method: Option<Method> = Method::new("GET");
+    ///     // Create a new HTTP Method instance (in our case, Method::GET)
+    ///     let method: Option<Method> = Method::new("GET");
     ///
-    /// 	assert_eq!(method, Some(Method::GET));
+    ///     assert_eq!(method, Some(Method::GET));
     /// }
     /// ```
     pub fn new<S>(method: S) -> Option<Self>
@@ -127,6 +344,10 @@ impl Method {
             "POST" => Some(Self::POST),
             "PUT" => Some(Self::PUT),
             "DELETE" => Some(Self::DELETE),
+            "PATCH" => Some(Self::PATCH),
+            "OPTIONS" => Some(Self::OPTIONS),
+            "TRACE" => Some(Self::TRACE),
+            "CONNECT" => Some(Self::CONNECT),
             _ => None,
         }
     }
@@ -143,7 +364,241 @@ impl fmt::Display for Method {
                 Self::POST => "POST",
                 Self::PUT => "PUT",
                 Self::DELETE => "DELETE",
+                Self::PATCH => "PATCH",
+                Self::OPTIONS => "OPTIONS",
+                Self::TRACE => "TRACE",
+                Self::CONNECT => "CONNECT",
+            }
+        )
+    }
+}
+
+/// The value of the `SameSite` attribute of a [`Cookie`](crate::Cookie)
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum SameSite {
+    /// The cookie is only sent along with same-site requests
+    Strict,
+    /// The cookie is also sent when the user navigates to the site from another one
+    Lax,
+    /// The cookie is sent along with all requests. Such a cookie must also be secure
+    None,
+}
+
+impl fmt::Display for SameSite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Strict => "Strict",
+                Self::Lax => "Lax",
+                Self::None => "None",
             }
         )
     }
 }
+
+/// The reason [`Request::json()`](crate::Request::json) failed
+#[cfg(feature = "json")]
+#[derive(Debug)]
+pub enum JsonBodyError {
+    /// The media type of the body isn't JSON
+    UnsupportedMediaType,
+    /// The body isn't valid JSON or doesn't match the expected type
+    Invalid(serde_json::Error),
+}
+
+#[cfg(feature = "json")]
+impl JsonBodyError {
+    /// Returns the [`Status`] a handler would usually respond with because of this error
+    pub fn status(&self) -> Status {
+        match self {
+            Self::UnsupportedMediaType => Status::UnsupportedMediaType,
+            Self::Invalid(_) => Status::BadRequest,
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl fmt::Display for JsonBodyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnsupportedMediaType => write!(f, "the request body isn't JSON"),
+            Self::Invalid(error) => write!(f, "invalid JSON request body: {}", error),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl std::error::Error for JsonBodyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Invalid(error) => Some(error),
+            Self::UnsupportedMediaType => None,
+        }
+    }
+}
+
+/// An error returned by a request handler, which the server turns into an error response (see [`HandlerResult`](crate::HandlerResult))
+///
+/// Errors with a message send it as a plain text body, while the others are sent without a body, which lets the handler set with [`Server::on_error()`](crate::Server::on_error) respond to server errors
+///
+/// # Example
+///
+/// ```
+/// use oak_http_server::{ResponseError, Server};
+/// use std::fs;
+/// use std::io::{Read, Write};
+/// use std::net::TcpStream;
+///
+/// fn main() {
+///     let mut server = Server::new("localhost", 0 as u16);
+///
+///     server.on_get("/notes", |request, response| {
+///         let Some(name) = request.target.queries.get("name") else {
+///             return Err(ResponseError::BadRequest(String::from("Missing note name")));
+///         };
+///         if name.contains('/') {
+///             return Err(ResponseError::Forbidden);
+///         }
+///
+///         // A missing file results in a 404 Not Found response
+///         response.send(fs::read_to_string(format!("/nonexistent/notes/{}.txt", name))?);
+///         Ok(())
+///     });
+///
+///     let handle = server.start_with_handle().unwrap();
+///     let send = |request: &str| {
+///         let mut stream = TcpStream::connect(handle.local_address()).unwrap();
+///         stream.write_all(request.as_bytes()).unwrap();
+///
+///         let mut response = String::new();
+///         let _ = stream.read_to_string(&mut response);
+///         response
+///     };
+///
+///     let response = send("GET /notes HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+///     assert!(response.starts_with("HTTP/1.1 400"));
+///     assert!(response.ends_with("Missing note name"));
+///
+///     let response = send("GET /notes?name=../secret HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+///     assert!(response.starts_with("HTTP/1.1 403"));
+///
+///     let response = send("GET /notes?name=shopping HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+///     assert!(response.starts_with("HTTP/1.1 404"));
+///
+///     handle.stop();
+/// }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ResponseError {
+    /// `400 Bad Request`, with a message describing the problem with the request
+    BadRequest(String),
+    /// `403 Forbidden`
+    Forbidden,
+    /// `404 Not Found`
+    NotFound,
+    /// `500 Internal Server Error`, caused by the given error. The error is logged, but isn't sent to the client
+    Internal(Box<dyn std::error::Error + Send + Sync>),
+    /// Any other status, optionally with a message
+    Status(Status, Option<String>),
+}
+
+impl ResponseError {
+    /// Returns the [`Status`] of the error response
+    pub fn status(&self) -> Status {
+        match self {
+            Self::BadRequest(_) => Status::BadRequest,
+            Self::Forbidden => Status::Forbidden,
+            Self::NotFound => Status::NotFound,
+            Self::Internal(_) => Status::InternalError,
+            Self::Status(status, _) => *status,
+        }
+    }
+
+    /// Returns the message sent as the body of the error response, if any
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            Self::BadRequest(message) => Some(message),
+            Self::Status(_, message) => message.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BadRequest(message) => write!(f, "bad request: {}", message),
+            Self::Forbidden => write!(f, "access to the resource is forbidden"),
+            Self::NotFound => write!(f, "the resource wasn't found"),
+            Self::Internal(error) => write!(f, "{}", error),
+            Self::Status(status, Some(message)) => write!(f, "status {}: {}", status, message),
+            Self::Status(status, None) => write!(f, "status {}", status),
+        }
+    }
+}
+
+impl std::error::Error for ResponseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Internal(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ResponseError {
+    /// Errors caused by a missing file or a lack of permissions result in `404 Not Found` and `403 Forbidden` responses respectively, while any other error is an internal error
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => Self::NotFound,
+            io::ErrorKind::PermissionDenied => Self::Forbidden,
+            _ => Self::Internal(Box::new(error)),
+        }
+    }
+}
+
+/// The reason [`Request::save_body_to()`](crate::Request::save_body_to) failed
+#[derive(Debug)]
+pub enum SaveBodyError {
+    /// The body is larger than the maximum size allowed
+    TooLarge,
+    /// The media type of the body isn't one of the allowed ones
+    UnsupportedMediaType,
+    /// The file couldn't be written
+    Io(io::Error),
+}
+
+impl SaveBodyError {
+    /// Returns the [`Status`] a handler would usually respond with because of this error
+    pub fn status(&self) -> Status {
+        match self {
+            Self::TooLarge => Status::ContentTooLarge,
+            Self::UnsupportedMediaType => Status::UnsupportedMediaType,
+            Self::Io(_) => Status::InternalError,
+        }
+    }
+}
+
+impl fmt::Display for SaveBodyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TooLarge => write!(f, "the request body is too large"),
+            Self::UnsupportedMediaType => {
+                write!(f, "the media type of the request body isn't allowed")
+            }
+            Self::Io(error) => write!(f, "couldn't save the request body: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for SaveBodyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
@@ -1,27 +1,177 @@
 //! Includes various handlers provided by the library
 
 use std::fs;
+use std::path::{Component, Path, PathBuf};
 
-use crate::{Request, Response};
+use crate::utils::{html_escape, is_unreserved, percent_encode};
+use crate::{HandlerResult, Request, Response, ResponseError, Status};
 
-fn read_file(parent_dir: String, request: Request, mut response: Response) {
-    match fs::read_to_string(
-        parent_dir.chars().skip(1).collect::<String>() + &request.target.relative_path,
-    ) {
-        Ok(contents) => response.send(contents),
-        Err(error) => {
-            use crate::enums::Status;
-            use std::io::ErrorKind;
+/// Options for the directory handlers created with [`read_same_dir_with_options()`] and [`read_diff_dir_with_options()`]
+#[derive(Clone, Debug)]
+pub struct DirOptions {
+    /// The file that is served when a directory is requested, if it exists (`index.html` by default). If [`None`], no index file is served
+    pub index: Option<String>,
+    /// Whether an HTML page listing the entries of a directory is sent when it has no index file (disabled by default)
+    pub listing: bool,
+}
 
-            let status: Status = match error.kind() {
-                ErrorKind::NotFound => Status::NotFound,
-                _ => Status::InternalError,
-            };
+impl Default for DirOptions {
+    fn default() -> Self {
+        Self {
+            index: Some(String::from("index.html")),
+            listing: false,
+        }
+    }
+}
+
+fn read_file(parent_dir: &Path, options: &DirOptions, request: Request, mut response: Response) {
+    let Some(mut path) = resolve_path(parent_dir, &request.target.relative_path) else {
+        eprintln!(
+            "Path traversal attempt detected at '{}'. Responding with a 403 status code...",
+            request.target.path()
+        );
+        response.status(Status::Forbidden);
+        response.end();
+        return;
+    };
+
+    if path.is_dir() {
+        // Relative links in the page of a directory only work if its URL ends with a slash
+        if !request.target.path().ends_with('/') {
+            // The path has been decoded, so it must be encoded again for the redirect to lead to the same directory. The query is kept as well
+            let mut location = percent_encode(request.target.path(), |byte| {
+                is_unreserved(byte) || byte == b'/'
+            });
+            location.push('/');
+            if let Some((_, query)) = request.target.request_target().split_once('?') {
+                location.push('?');
+                location.push_str(query);
+            }
+
+            response.redirect(location, Status::MovedPermanently);
+            return;
+        }
+
+        match options
+            .index
+            .as_ref()
+            .map(|index| path.join(index))
+            .filter(|index| index.is_file())
+        {
+            Some(index) => path = index,
+            None if options.listing => return send_listing(&path, &request, response),
+            None => {
+                response.status(Status::NotFound);
+                response.end();
+                return;
+            }
+        }
+    }
+
+    // The file is streamed, so binary and large files are served without being loaded in memory as a whole
+    if let Err((mut response, error)) = response.send_file(&request, &path) {
+        use std::io::ErrorKind;
+
+        let status: Status = match error.kind() {
+            ErrorKind::NotFound => Status::NotFound,
+            _ => Status::InternalError,
+        };
+
+        response.status(status);
+        response.end();
+    }
+}
 
-            response.status(status);
+/// Resolve the relative path of a request to a path inside the parent directory, returning [`None`] if it would point outside of it
+fn resolve_path(parent_dir: &Path, relative_path: &str) -> Option<PathBuf> {
+    // Append each segment of the URL path to the parent directory, so that the result is a valid path on every platform.
+    // A segment must be a plain file name, since `..`, a root or a platform-specific separator in it could lead out of the directory
+    let mut path = parent_dir.to_path_buf();
+    for segment in relative_path
+        .split('/')
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+    {
+        let mut components = Path::new(segment).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(name)), None) if name == segment => path.push(name),
+            _ => return None,
+        }
+    }
+
+    // Symbolic links may still point outside of the directory. A path that doesn't exist can't be served anyway
+    if let (Ok(canonical_parent), Ok(canonical_path)) =
+        (parent_dir.canonicalize(), path.canonicalize())
+    {
+        if !canonical_path.starts_with(canonical_parent) {
+            return None;
+        }
+    }
+
+    Some(path)
+}
+
+/// Send an HTML page linking to each entry of a directory
+fn send_listing(path: &Path, request: &Request, mut response: Response) {
+    let mut entries: Vec<String> = match fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| {
+                let mut name = entry.file_name().to_string_lossy().into_owned();
+                // Mark subdirectories with a trailing slash, so that their links point to the right place
+                if entry.path().is_dir() {
+                    name.push('/');
+                }
+                name
+            })
+            .collect(),
+        Err(_) => {
+            response.status(Status::InternalError);
             response.end();
+            return;
         }
+    };
+    entries.sort();
+
+    // The root of the served directory has no parent to link to
+    if request
+        .target
+        .relative_path
+        .split('/')
+        .any(|segment| !segment.is_empty())
+    {
+        entries.insert(0, String::from("../"));
     }
+
+    let title = html_escape(request.target.path());
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Index of {0}</title>\n</head>\n<body>\n<h1>Index of {0}</h1>\n<ul>\n",
+        title
+    );
+    for name in entries {
+        let link = percent_encode(&name, |byte| is_unreserved(byte) || byte == b'/');
+        page.push_str(&format!(
+            "<li><a href=\"{}\">{}</a></li>\n",
+            link,
+            html_escape(&name)
+        ));
+    }
+    page.push_str("</ul>\n</body>\n</html>\n");
+
+    response.headers.insert(
+        String::from("Content-Type"),
+        String::from("text/html; charset=utf-8"),
+    );
+    response.send(page);
+}
+
+/// Returns the local directory corresponding to the path of a directory handler, relative to the current working directory
+fn same_dir(request: &Request) -> PathBuf {
+    request
+        .target
+        .target_path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect()
 }
 
 /// Read a file from the same directory as the one specified during the handler's creation
@@ -32,40 +182,359 @@ fn read_file(parent_dir: String, request: Request, mut response: Response) {
 /// use oak_http_server::{handlers::read_same_dir, Server};
 ///
 /// fn main() {
-///	    let hostname = "localhost";
+///     let hostname = "localhost";
 ///     let port: u16 = 2300;
 ///
 ///     let mut server = Server::new(hostname, port);
-///		// If the server were to be started, any content the server would provide for the `/www` directory would be readen from the local `www` directory
+///     // If the server were to be started, any content the server would provide for the `/www` directory would be readen from the local `www` directory
 ///     server.on_directory("/www", read_same_dir);
 /// }
 /// ```
 pub fn read_same_dir(request: Request, response: Response) {
-    read_file(request.target.target_path.clone(), request, response)
+    read_file(
+        &same_dir(&request),
+        &DirOptions::default(),
+        request,
+        response,
+    )
+}
+
+/// Same as [`read_same_dir`], but with custom [`DirOptions`]
+///
+/// # Example:
+///
+/// ```
+/// use oak_http_server::{
+///     handlers::{read_same_dir_with_options, DirOptions},
+///     Server,
+/// };
+///
+/// fn main() {
+///     let hostname = "localhost";
+///     let port: u16 = 2300;
+///
+///     let mut server = Server::new(hostname, port);
+///     // Directories under `/files` without an `index.html` file are answered with a listing of their entries
+///     server.on_directory(
+///         "/files",
+///         read_same_dir_with_options(DirOptions {
+///             listing: true,
+///             ..Default::default()
+///         }),
+///     );
+/// }
+/// ```
+pub fn read_same_dir_with_options(options: DirOptions) -> impl Fn(Request, Response) {
+    move |request: Request, response: Response| {
+        read_file(&same_dir(&request), &options, request, response)
+    }
 }
 
 /// Read a file from the directory different than the one specified during the handler's creation
 ///
+/// The directory can be either a relative path (resolved relative to the current working directory) or an absolute one
+///
+/// Files are sent with [`Response::send_file()`], so they are streamed, their `Content-Type` is inferred from their extension and `Range` requests are honored.
+/// The same applies to [`read_same_dir`]
+///
 /// # Example:
 ///
 /// ```
 /// use oak_http_server::{handlers::read_diff_dir, Server};
 ///
 /// fn main() {
-///	    let hostname = "localhost";
+///     let hostname = "localhost";
 ///     let port: u16 = 2300;
 ///
 ///     let mut server = Server::new(hostname, port);
-///		// If the server were to be started, any content the server would provide for the `/www` directory would be readen from the local `etc` directory
+///     // If the server were to be started, any content the server would provide for the `/www` directory would be readen from the local `etc` directory
 ///     server.on_directory("/www", read_diff_dir("etc"));
 /// }
 /// ```
+///
+/// Binary files are sent as they are:
+///
+/// ```
+/// use oak_http_server::{handlers::read_diff_dir, Server};
+/// use std::fs;
+/// use std::io::{Read, Write};
+/// use std::net::TcpStream;
+///
+/// fn main() {
+///     let dir = std::env::temp_dir().join(format!("oak-http-server-{}", std::process::id()));
+///     fs::create_dir_all(dir.join("public")).unwrap();
+///     let image = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0xFF, 0xFE];
+///     fs::write(dir.join("public").join("pixel.png"), image).unwrap();
+///     fs::write(dir.join("secret.txt"), "password").unwrap();
+///
+///     let mut server = Server::new("localhost", 0 as u16);
+///     server.on_directory("/static", read_diff_dir(dir.join("public")));
+///
+///     let handle = server.start_with_handle().unwrap();
+///     let get = |path: &str, headers: &str| {
+///         let mut stream = TcpStream::connect(handle.local_address()).unwrap();
+///         let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n", path, headers);
+///         stream.write_all(request.as_bytes()).unwrap();
+///
+///         let mut response = Vec::new();
+///         stream.read_to_end(&mut response).unwrap();
+///         response
+///     };
+///     let contains = |response: &[u8], text: &str| response.windows(text.len()).any(|window| window == text.as_bytes());
+///
+///     let response = get("/static/pixel.png", "");
+///     assert!(response.starts_with(b"HTTP/1.1 200"));
+///     assert!(contains(&response, "Content-Type: image/png"));
+///     assert!(contains(&response, "Accept-Ranges: bytes"));
+///     assert!(response.ends_with(&image));
+///
+///     // Parts of a file can be requested, to resume downloads or seek in media
+///     let response = get("/static/pixel.png", "Range: bytes=2-5\r\n");
+///     assert!(response.starts_with(b"HTTP/1.1 206"));
+///     assert!(contains(&response, "Content-Range: bytes 2-5/11"));
+///     assert!(response.ends_with(&image[2..6]));
+///
+///     let response = get("/static/pixel.png", "Range: bytes=20-30\r\n");
+///     assert!(response.starts_with(b"HTTP/1.1 416"));
+///     assert!(contains(&response, "Content-Range: bytes */11"));
+///
+///     // Requests can't escape the directory
+///     for path in ["/static/../secret.txt", "/static/%2e%2e/secret.txt", "/static/..%2Fsecret.txt"] {
+///         assert!(get(path, "").starts_with(b"HTTP/1.1 403"));
+///     }
+///
+///     handle.stop();
+///     fs::remove_dir_all(&dir).unwrap();
+/// }
+/// ```
+pub fn read_diff_dir<P>(parent_dir: P) -> impl Fn(Request, Response)
+where
+    P: Into<PathBuf>,
+{
+    read_diff_dir_with_options(parent_dir, DirOptions::default())
+}
+
+/// Same as [`read_diff_dir()`], but with custom [`DirOptions`]
+///
+/// # Example:
+///
+/// ```
+/// use oak_http_server::{
+///     handlers::{read_diff_dir_with_options, DirOptions},
+///     Server,
+/// };
+/// use std::fs;
+/// use std::io::{Read, Write};
+/// use std::net::TcpStream;
+///
+/// fn main() {
+///     let dir = std::env::temp_dir().join(format!("oak-http-server-listing-{}", std::process::id()));
+///     fs::create_dir_all(dir.join("docs")).unwrap();
+///     fs::create_dir_all(dir.join("site")).unwrap();
+///     fs::create_dir_all(dir.join("50% off")).unwrap();
+///     fs::write(dir.join("docs").join("a&b.txt"), "A and B").unwrap();
+///     fs::write(dir.join("site").join("home.html"), "<h1>Home</h1>").unwrap();
+///
+///     let mut server = Server::new("localhost", 0 as u16);
+///     server.on_directory(
+///         "/static",
+///         read_diff_dir_with_options(
+///             dir.clone(),
+///             DirOptions {
+///                 index: Some(String::from("home.html")),
+///                 listing: true,
+///             },
+///         ),
+///     );
+///
+///     let handle = server.start_with_handle().unwrap();
+///     let get = |path: &str| {
+///         let mut stream = TcpStream::connect(handle.local_address()).unwrap();
+///         let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path);
+///         stream.write_all(request.as_bytes()).unwrap();
+///
+///         let mut response = String::new();
+///         stream.read_to_string(&mut response).unwrap();
+///         response
+///     };
+///
+///     // A directory is served through its index file
+///     assert!(get("/static/site/").ends_with("<h1>Home</h1>"));
+///
+///     // A directory without one is listed
+///     let listing = get("/static/docs/");
+///     assert!(listing.contains("<a href=\"a%26b.txt\">a&amp;b.txt</a>"));
+///     assert!(listing.contains("<a href=\"../\">"));
+///
+///     // The URL of a directory must end with a slash
+///     assert!(get("/static/docs").starts_with("HTTP/1.1 301"));
+///
+///     // The redirect keeps the query and leads to the same directory, even if its name contains reserved characters
+///     let redirect = get("/static/50%25%20off?sort=name");
+///     assert!(redirect.contains("Location: /static/50%25%20off/?sort=name\r\n"));
+///
+///     handle.stop();
+///     fs::remove_dir_all(&dir).unwrap();
+/// }
+/// ```
+pub fn read_diff_dir_with_options<P>(
+    parent_dir: P,
+    options: DirOptions,
+) -> impl Fn(Request, Response)
+where
+    P: Into<PathBuf>,
+{
+    let parent_dir: PathBuf = parent_dir.into();
 
-pub fn read_diff_dir<S>(parent_dir: S) -> impl Fn(Request, Response)
+    move |request: Request, response: Response| read_file(&parent_dir, &options, request, response)
+}
+
+/// Send a specific file, regardless of the target of the request
+///
+/// The file is opened when a request arrives, so it may be created or replaced while the server is running. If it doesn't exist, the server responds with a `404 Not Found` status.
+/// The file is sent with [`Response::send_file()`], so its `Content-Type` is inferred from its extension and `Range` requests are honored
+///
+/// # Example:
+///
+/// ```
+/// use oak_http_server::{handlers::read_file_at, Server};
+///
+/// fn main() {
+///     let hostname = "localhost";
+///     let port: u16 = 2300;
+///
+///     let mut server = Server::new(hostname, port);
+///     // If the server were to be started, requests to `/robots.txt` would be answered with the contents of the local `static/robots.txt` file
+///     server.on_get("/robots.txt", read_file_at("static/robots.txt"));
+/// }
+/// ```
+///
+/// Files are sent along with `ETag` and `Last-Modified` headers, so that clients can revalidate their cached copies with a conditional request:
+///
+/// ```
+/// use oak_http_server::{handlers::read_file_at, Server};
+/// use std::fs;
+/// use std::io::{Read, Write};
+/// use std::net::TcpStream;
+///
+/// fn main() {
+///     let file = std::env::temp_dir().join(format!("oak-http-server-robots-{}.txt", std::process::id()));
+///     fs::write(&file, "User-agent: *\nDisallow:\n").unwrap();
+///
+///     let mut server = Server::new("localhost", 0 as u16);
+///     server.on_get("/robots.txt", read_file_at(file.clone()));
+///
+///     let handle = server.start_with_handle().unwrap();
+///     let get = |headers: &str| {
+///         let mut stream = TcpStream::connect(handle.local_address()).unwrap();
+///         let request = format!("GET /robots.txt HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n", headers);
+///         stream.write_all(request.as_bytes()).unwrap();
+///
+///         let mut response = String::new();
+///         stream.read_to_string(&mut response).unwrap();
+///         response
+///     };
+///     let header = |response: &str, name: &str| {
+///         response
+///             .lines()
+///             .find_map(|line| line.strip_prefix(&format!("{}: ", name)))
+///             .unwrap()
+///             .to_string()
+///     };
+///
+///     let response = get("");
+///     assert!(response.starts_with("HTTP/1.1 200"));
+///     let etag = header(&response, "ETag");
+///     let last_modified = header(&response, "Last-Modified");
+///
+///     let response = get(&format!("If-None-Match: {}\r\n", etag));
+///     assert!(response.starts_with("HTTP/1.1 304"));
+///     assert!(response.ends_with("\r\n\r\n"));
+///
+///     let response = get(&format!("If-Modified-Since: {}\r\n", last_modified));
+///     assert!(response.starts_with("HTTP/1.1 304"));
+///
+///     let response = get("If-Modified-Since: Thu, 01 Jan 2004 00:00:00 GMT\r\n");
+///     assert!(response.starts_with("HTTP/1.1 200"));
+///
+///     handle.stop();
+///     fs::remove_file(&file).unwrap();
+/// }
+/// ```
+pub fn read_file_at<P>(file: P) -> impl Fn(Request, Response)
 where
-    S: Into<String> + Clone,
+    P: Into<PathBuf>,
 {
+    let file: PathBuf = file.into();
+
     move |request: Request, response: Response| {
-        read_file(parent_dir.clone().into(), request, response)
+        if let Err((mut response, error)) = response.send_file(&request, &file) {
+            use std::io::ErrorKind;
+
+            response.status(match error.kind() {
+                ErrorKind::NotFound => Status::NotFound,
+                _ => Status::InternalError,
+            });
+            response.end();
+        }
+    }
+}
+
+/// Wrap a handler so that requests with a body of a media type not included in `allowed` are rejected with a `415 Unsupported Media Type` status
+///
+/// Media types are compared case-insensitively and any parameters (like `charset`) are ignored. Requests without a body are always passed to the handler,
+/// whose result is returned as is (see [`HandlerResult`])
+///
+/// # Example:
+///
+/// ```
+/// use oak_http_server::{handlers::require_content_type, ResponseError, Server};
+///
+/// fn main() {
+///     let hostname = "localhost";
+///     let port: u16 = 2300;
+///
+///     let mut server = Server::new(hostname, port);
+///     // Only JSON request bodies will reach the handler
+///     server.on_post(
+///         "/api/users",
+///         require_content_type(&["application/json"], |request, response| {
+///             if !request.body.starts_with(b"{") {
+///                 return Err(ResponseError::BadRequest(String::from("Expected a JSON object")));
+///             }
+///
+///             response.send("User created");
+///             Ok(())
+///         }),
+///     );
+/// }
+/// ```
+pub fn require_content_type<F, R>(
+    allowed: &[&str],
+    handler: F,
+) -> impl Fn(Request, Response) -> Result<(), ResponseError>
+where
+    F: Fn(Request, Response) -> R,
+    R: HandlerResult,
+{
+    let allowed: Vec<String> = allowed
+        .iter()
+        .map(|media_type| media_type.to_ascii_lowercase())
+        .collect();
+
+    move |request: Request, mut response: Response| {
+        if !request.body.is_empty() {
+            let is_allowed = request
+                .content_type()
+                .is_some_and(|media_type| allowed.contains(&media_type));
+
+            if !is_allowed {
+                response.status(Status::UnsupportedMediaType);
+                response.end();
+                return Ok(());
+            }
+        }
+
+        handler(request, response).into_result()
     }
 }
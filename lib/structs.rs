@@ -1,7 +1,14 @@
-use std::{collections::HashMap, fmt};
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    time::{Duration, SystemTime},
+};
+
+use crate::{format_time, utils::*, Method, SameSite, Status};
 
 /// The HTTP version of a request or a response
-#[derive(PartialEq, Clone)]
+#[derive(PartialEq, Clone, Debug)]
 pub struct Version {
     /// The major revision number of the HTTP version
     pub major: usize,
@@ -22,8 +29,8 @@ impl Version {
     /// # use oak_http_server::Version;
     ///
     /// fn main() {
-    /// 	let version = Version::new("HTTP/1.1").unwrap(); // Unwrap the `Some` value the `new` function returns
-    /// 	println!("{}", version); // Prints "HTTP/1.1" in the console
+    ///     let version = Version::new("HTTP/1.1").unwrap(); // Unwrap the `Some` value the `new` function returns
+    ///     println!("{}", version); // Prints "HTTP/1.1" in the console
     /// }
     /// ```
     pub fn new<S>(version: S) -> Option<Self>
@@ -32,27 +39,21 @@ impl Version {
     {
         let version = version.into();
 
-        if version.len() >= 5 {
-            if &version[0..4] == "HTTP" && &version[4..5] == "/" {
-                let version_split = &mut version[5..].split(".");
-                if version_split.clone().count() == 2 {
-                    let parse_int = |option_input: Option<&str>| -> Option<usize> {
-                        let Some(string_num) = option_input else {
-							return None;
-						};
-
-                        let Ok(number) = string_num.parse::<usize>() else {
-							return None;
-						};
-
-                        Some(number)
+        if let Some(numbers) = version.strip_prefix("HTTP/") {
+            let version_split = &mut numbers.split(".");
+            if version_split.clone().count() == 2 {
+                let parse_int = |option_input: Option<&str>| -> Option<usize> {
+                    let Ok(number) = option_input?.parse::<usize>() else {
+                        return None;
                     };
 
-                    let Some(major) = parse_int(version_split.next()) else {return None};
-                    let Some(minor) = parse_int(version_split.next()) else {return None};
+                    Some(number)
+                };
 
-                    return Some(Self { major, minor });
-                }
+                let major = parse_int(version_split.next())?;
+                let minor = parse_int(version_split.next())?;
+
+                return Some(Self { major, minor });
             }
         }
 
@@ -67,85 +68,158 @@ impl fmt::Display for Version {
 }
 
 /// Represents a HTTP URL (named [`Target`] for formality reasons)
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Target {
     /// Contains the path of the current handler (Empty by default. Modified by the server before being passed to a handler). Primarily used by directory handlers.
     ///
     /// For example, if a directory handler is assigned at path `\www\etc` and the client attempts to access `\www\etc\main.txt`,
     /// this field's String's contents  will be `\www\etc` and the [relative path](Self::relative_path) will be equal to `\main.txt`
+    ///
+    /// To obtain the whole path the client requested, use [`path()`](Self::path)
     pub target_path: String,
     /// Check the [target path](Self::target_path) documentation
     pub relative_path: String,
     /// A HashMap with a String key representing the query value and a String value representing the query value (query is defined in RFC 3986 as well)
+    ///
+    /// If a query is repeated, only its first value is stored here. Check [`queries_all`](Self::queries_all) for all of its values
     pub queries: HashMap<String, String>,
+    /// A HashMap with a String key representing the query name and a Vec of all the values given to it, in the order they appear in the target
+    pub queries_all: HashMap<String, Vec<String>>,
+
+    path: String,
 }
 
 impl Target {
     /// Parses a [`&str`] or [`String`] into a [`Target`]
+    ///
+    /// Queries without a value, like flags, are stored with an empty value. The names and values of queries are decoded individually
+    /// according to the `application/x-www-form-urlencoded` rules, so a `+` stands for a space, while the path is only percent-decoded
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use oak_http_server::Target;
+    ///
+    /// fn main() {
+    ///     let target = Target::new("/search?verbose&page=2");
+    ///
+    ///     assert_eq!(target.queries.get("verbose").map(String::as_str), Some(""));
+    ///     assert_eq!(target.queries.get("page").map(String::as_str), Some("2"));
+    ///
+    ///     let target = Target::new("/a+b%20c?q=rust+http%2Bserver&expr=1%2B1%3D2&list=a%26b&odd%3Dname");
+    ///
+    ///     assert_eq!(target.path(), "/a+b c");
+    ///     assert_eq!(target.queries.get("q").map(String::as_str), Some("rust http+server"));
+    ///     assert_eq!(target.queries.get("expr").map(String::as_str), Some("1+1=2"));
+    ///     assert_eq!(target.queries.get("list").map(String::as_str), Some("a&b"));
+    ///     assert_eq!(target.queries.get("odd=name").map(String::as_str), Some(""));
+    ///
+    ///     // Multi-byte UTF-8 sequences are decoded as a whole, while malformed escapes are kept as they are
+    ///     let target = Target::new("/caf%C3%A9?discount=100%&code=%zz");
+    ///
+    ///     assert_eq!(target.path(), "/café");
+    ///     assert_eq!(target.queries.get("discount").map(String::as_str), Some("100%"));
+    ///     assert_eq!(target.queries.get("code").map(String::as_str), Some("%zz"));
+    ///
+    ///     // Repeated queries keep all of their values, in order
+    ///     let target = Target::new("/posts?tag=rust&tag=http&page=2");
+    ///
+    ///     assert_eq!(target.queries.get("tag").map(String::as_str), Some("rust"));
+    ///     assert_eq!(target.queries_all.get("tag"), Some(&vec!["rust".to_string(), "http".to_string()]));
+    ///     assert_eq!(target.to_string().matches("tag=").count(), 2);
+    /// }
+    /// ```
     pub fn new<S>(target: S) -> Self
     where
         S: Into<String>,
     {
-        let target_string: String = Self::decode_url(target.into());
+        let target_string: String = target.into();
 
+        // Split the target before decoding it, so that encoded delimiters aren't mistaken for real ones
         let (absolute_path, queries_str) = target_string
             .split_once('?')
             .unwrap_or((&target_string, ""));
+        let absolute_path = percent_decode(absolute_path);
 
         let mut queries = HashMap::new();
+        let mut queries_all: HashMap<String, Vec<String>> = HashMap::new();
 
-        if !queries_str.is_empty() {
-            let queries_split = queries_str.split("&");
-
-            for query_str in queries_split {
-                if let Some((name, value)) = query_str.split_once("=") {
-                    queries.insert(name.to_string(), value.to_string());
-                }
-            }
+        for (name, value) in parse_urlencoded(queries_str) {
+            queries.entry(name.clone()).or_insert_with(|| value.clone());
+            queries_all.entry(name).or_default().push(value);
         }
 
         Self {
             target_path: String::new(),
-            relative_path: absolute_path.to_string(),
+            relative_path: absolute_path.clone(),
             queries,
+            queries_all,
+
+            path: absolute_path,
         }
     }
 
+    /// Returns the decoded path of the request, without the query
+    ///
+    /// Unlike [`full_url()`](Self::full_url), which joins the [target path](Self::target_path) and the [relative path](Self::relative_path),
+    /// this is always the path the client requested, even if a directory handler has split it (or the fields have been modified in any other way)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use oak_http_server::Target;
+    ///
+    /// fn main() {
+    ///     let target = Target::new("/www/hello%20world.txt?lang=en");
+    ///
+    ///     assert_eq!(target.path(), "/www/hello world.txt");
+    /// }
+    /// ```
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
     /// Returns the URL path, according to RFC 3986
     pub fn full_url(&self) -> String {
         format!("{}{}", &self.target_path, &self.relative_path)
     }
 
-    fn decode_url(encoded_url: String) -> String {
-        let mut url_iterator = encoded_url.split("%");
-
-        [
-            url_iterator.next().unwrap().to_string(),
-            url_iterator
-                .map(|str_to_decode| {
-                    if str_to_decode.len() >= 2 {
-                        if str_to_decode[..2]
-                            .chars()
-                            .all(|char_to_check| char_to_check.is_digit(16))
-                        {
-                            let mut concatenated_string = String::new();
-                            concatenated_string.push(
-                                char::from_u32(
-                                    u32::from_str_radix(&str_to_decode[..2], 16).unwrap(),
-                                )
-                                .unwrap(),
-                            );
-                            concatenated_string.push_str(&str_to_decode[2..]);
-                            return concatenated_string;
-                        }
-                    }
+    /// Returns the target in the form it is sent in a request line, percent-encoding any characters that aren't allowed in the path or the query
+    pub(crate) fn request_target(&self) -> String {
+        let mut request_target = percent_encode(&self.full_url(), |byte| {
+            is_unreserved(byte) || b"!$&'()*+,;=:@/".contains(&byte)
+        });
 
-                    str_to_decode.to_string()
+        for (index, (name, value)) in self.query_pairs().into_iter().enumerate() {
+            let encode_component = |component: &str| {
+                percent_encode(component, |byte| {
+                    is_unreserved(byte) || b"!$'()*,;:@/?".contains(&byte)
                 })
-                .collect::<Vec<String>>()
-                .join(""),
-        ]
-        .join("")
+            };
+
+            request_target.push(if index == 0 { '?' } else { '&' });
+            request_target.push_str(&encode_component(name));
+            request_target.push('=');
+            request_target.push_str(&encode_component(value));
+        }
+
+        request_target
+    }
+
+    /// Returns the name-value pairs of the queries. A query keeps all of its [values](Self::queries_all), unless its [first value](Self::queries) has been changed
+    fn query_pairs(&self) -> Vec<(&str, &str)> {
+        let mut pairs = Vec::new();
+
+        for (name, value) in &self.queries {
+            match self.queries_all.get(name) {
+                Some(values) if values.first() == Some(value) => {
+                    pairs.extend(values.iter().map(|value| (name.as_str(), value.as_str())))
+                }
+                _ => pairs.push((name.as_str(), value.as_str())),
+            }
+        }
+
+        pairs
     }
 }
 
@@ -153,13 +227,13 @@ impl fmt::Display for Target {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}{}", self.full_url(), {
             let mut queries_string = self
-                .queries
-                .iter()
-                .map(|(name, value)| format!("{}: {}&", name, value))
+                .query_pairs()
+                .into_iter()
+                .map(|(name, value)| format!("{}={}&", name, value))
                 .collect::<String>();
 
             if !queries_string.is_empty() {
-                queries_string.insert_str(0, "?");
+                queries_string.insert(0, '?');
                 queries_string.pop();
             }
 
@@ -167,3 +241,968 @@ impl fmt::Display for Target {
         })
     }
 }
+
+/// An entity tag, used as a validator of a specific representation of a resource (RFC 9110 §8.8.3)
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub struct ETag {
+    /// Whether the entity tag is weak (prefixed by `W/`), which means that it may be shared by semantically equivalent representations
+    pub weak: bool,
+    /// The opaque tag, without the surrounding double quotes
+    pub tag: String,
+}
+
+impl ETag {
+    /// Parses a [`&str`] or [`String`] in the format `"{tag}"` or `W/"{tag}"` into an [`ETag`]
+    ///
+    /// Returns [`None`] if the entity tag isn't quoted or contains invalid characters
+    pub fn new<S>(etag: S) -> Option<Self>
+    where
+        S: Into<String>,
+    {
+        let etag: String = etag.into();
+
+        let (weak, quoted) = match etag.strip_prefix("W/") {
+            Some(quoted) => (true, quoted),
+            None => (false, etag.as_str()),
+        };
+
+        let tag = quoted.strip_prefix('"')?.strip_suffix('"')?;
+
+        // Double quotes, whitespace and control characters aren't allowed in an entity tag
+        if tag
+            .bytes()
+            .any(|byte| byte == b'"' || byte <= b' ' || byte == 0x7F)
+        {
+            return None;
+        }
+
+        Some(Self {
+            weak,
+            tag: tag.to_string(),
+        })
+    }
+
+    /// Create a strong [`ETag`] from an opaque tag
+    pub fn strong<S>(tag: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            weak: false,
+            tag: tag.into(),
+        }
+    }
+
+    /// Create a weak [`ETag`] from an opaque tag
+    pub fn weak<S>(tag: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            weak: true,
+            tag: tag.into(),
+        }
+    }
+
+    /// Strong comparison: two entity tags are equivalent if neither of them is weak and their opaque tags match
+    ///
+    /// This is the comparison function used to evaluate `If-Match` and `If-Range` preconditions
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use oak_http_server::ETag;
+    ///
+    /// fn main() {
+    ///     // The comparison table of RFC 9110 §8.8.3.2
+    ///     assert!(!ETag::weak("1").strong_eq(&ETag::weak("1")));
+    ///     assert!(!ETag::weak("1").strong_eq(&ETag::weak("2")));
+    ///     assert!(!ETag::weak("1").strong_eq(&ETag::strong("1")));
+    ///     assert!(ETag::strong("1").strong_eq(&ETag::strong("1")));
+    /// }
+    /// ```
+    pub fn strong_eq(&self, other: &Self) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// Weak comparison: two entity tags are equivalent if their opaque tags match, regardless of whether either or both of them are weak
+    ///
+    /// This is the comparison function used to evaluate `If-None-Match` preconditions
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use oak_http_server::ETag;
+    ///
+    /// fn main() {
+    ///     // The comparison table of RFC 9110 §8.8.3.2
+    ///     assert!(ETag::weak("1").weak_eq(&ETag::weak("1")));
+    ///     assert!(!ETag::weak("1").weak_eq(&ETag::weak("2")));
+    ///     assert!(ETag::weak("1").weak_eq(&ETag::strong("1")));
+    ///     assert!(ETag::strong("1").weak_eq(&ETag::strong("1")));
+    /// }
+    /// ```
+    pub fn weak_eq(&self, other: &Self) -> bool {
+        self.tag == other.tag
+    }
+}
+
+impl fmt::Display for ETag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\"{}\"", if self.weak { "W/" } else { "" }, self.tag)
+    }
+}
+
+/// The parsed value of an `If-Match` or `If-None-Match` precondition header
+#[derive(PartialEq, Clone, Debug)]
+pub enum ETagCondition {
+    /// The header's value was `*`, which refers to any current representation of the resource
+    Any,
+    /// A list of entity tags
+    Tags(Vec<ETag>),
+}
+
+impl ETagCondition {
+    /// Parses the value of an `If-Match` or `If-None-Match` header into an [`ETagCondition`]
+    ///
+    /// Returns [`None`] if the value isn't either `*` or a comma-separated list of valid entity tags
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use oak_http_server::{ETag, ETagCondition};
+    ///
+    /// fn main() {
+    ///     let condition = ETagCondition::new(r#""xyzzy", W/"r2d2,xxxx""#);
+    ///
+    ///     assert_eq!(
+    ///         condition,
+    ///         Some(ETagCondition::Tags(vec![
+    ///             ETag::strong("xyzzy"),
+    ///             ETag::weak("r2d2,xxxx")
+    ///         ]))
+    ///     );
+    /// }
+    /// ```
+    pub fn new<S>(value: S) -> Option<Self>
+    where
+        S: Into<String>,
+    {
+        let value: String = value.into();
+        let mut remaining = value.trim();
+
+        if remaining == "*" {
+            return Some(Self::Any);
+        }
+
+        let mut tags = Vec::new();
+
+        while !remaining.is_empty() {
+            // Skip empty list elements, which are allowed by the list syntax (RFC 9110 §5.6.1)
+            if let Some(rest) = remaining.strip_prefix(',') {
+                remaining = rest.trim_start();
+                continue;
+            }
+
+            // An entity tag is an optional weakness indicator followed by an opaque quoted string. Since the opaque string can't contain a double quote, the tag ends at the next one
+            let opaque_start = if remaining.starts_with("W/") { 2 } else { 0 };
+            if !remaining[opaque_start..].starts_with('"') {
+                return None;
+            }
+            let opaque_end = opaque_start + 1 + remaining[opaque_start + 1..].find('"')?;
+
+            tags.push(ETag::new(&remaining[..=opaque_end])?);
+            remaining = remaining[opaque_end + 1..].trim_start();
+
+            // Each tag must be followed by either a comma or the end of the header
+            if !remaining.is_empty() && !remaining.starts_with(',') {
+                return None;
+            }
+        }
+
+        if tags.is_empty() {
+            None
+        } else {
+            Some(Self::Tags(tags))
+        }
+    }
+
+    /// Check whether an `If-Match` condition is fulfilled by the current entity tag of a resource, using the [strong comparison function](ETag::strong_eq)
+    pub fn matches_strong(&self, etag: &ETag) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Tags(tags) => tags.iter().any(|tag| tag.strong_eq(etag)),
+        }
+    }
+
+    /// Check whether an `If-None-Match` condition matches the current entity tag of a resource, using the [weak comparison function](ETag::weak_eq)
+    pub fn matches_weak(&self, etag: &ETag) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Tags(tags) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        }
+    }
+}
+
+/// A HTTP cookie, to be sent to the client in a `Set-Cookie` header (RFC 6265)
+///
+/// # Example
+///
+/// ```
+/// # use oak_http_server::{Cookie, SameSite};
+///
+/// fn main() {
+///     let mut cookie = Cookie::new("session", "38afes7a8");
+///     cookie.set_path("/");
+///     cookie.set_http_only(true);
+///     cookie.set_same_site(SameSite::Lax);
+///
+///     assert_eq!(
+///         cookie.to_string(),
+///         "session=38afes7a8; Path=/; HttpOnly; SameSite=Lax"
+///     );
+/// }
+/// ```
+#[derive(PartialEq, Clone)]
+pub struct Cookie {
+    /// The name of the cookie
+    pub name: String,
+    /// The value of the cookie
+    pub value: String,
+
+    domain: Option<String>,
+    path: Option<String>,
+    expires: Option<SystemTime>,
+    max_age: Option<u64>,
+    http_only: bool,
+    secure: bool,
+    same_site: Option<SameSite>,
+}
+
+impl Cookie {
+    /// Create a new [`Cookie`] with the provided name and value and no attributes
+    pub fn new<N, V>(name: N, value: V) -> Self
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            value: value.into(),
+
+            domain: None,
+            path: None,
+            expires: None,
+            max_age: None,
+            http_only: false,
+            secure: false,
+            same_site: None,
+        }
+    }
+
+    /// Parse a cookie in the format of a `Set-Cookie` header, such as one received from an upstream server, returning [`None`] if it doesn't start with a `name=value` pair
+    ///
+    /// Attribute names are case-insensitive, while attributes that are unknown or have an invalid value are ignored. Parsing the output of the [`Display`](fmt::Display) implementation returns an equal cookie, provided that its expiry time is a whole number of seconds
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use oak_http_server::{Cookie, SameSite};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// fn main() {
+    ///     let cookie = Cookie::parse(
+    ///         "id=a3fWa; Domain=example.com; Path=/docs; Expires=Sun, 06 Nov 1994 08:49:37 GMT; Max-Age=2592000; Secure; httponly; SameSite=Strict",
+    ///     )
+    ///     .unwrap();
+    ///
+    ///     let mut expected = Cookie::new("id", "a3fWa");
+    ///     expected.set_domain("example.com");
+    ///     expected.set_path("/docs");
+    ///     expected.set_expires(UNIX_EPOCH + Duration::from_secs(784111777));
+    ///     expected.set_max_age(2592000);
+    ///     expected.set_secure(true);
+    ///     expected.set_http_only(true);
+    ///     expected.set_same_site(SameSite::Strict);
+    ///     assert_eq!(cookie, expected);
+    ///
+    ///     // Serializing and parsing a cookie again returns the same cookie
+    ///     assert_eq!(Cookie::parse(&expected.to_string()), Some(expected));
+    ///
+    ///     // Invalid attributes are ignored, while a cookie without a name-value pair is rejected
+    ///     assert_eq!(
+    ///         Cookie::parse("theme=dark; Expires=never; SameSite=Sometimes"),
+    ///         Some(Cookie::new("theme", "dark"))
+    ///     );
+    ///     assert_eq!(Cookie::parse("HttpOnly; Secure"), None);
+    /// }
+    /// ```
+    pub fn parse(string: &str) -> Option<Self> {
+        let mut parts = string.split(';');
+
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let mut cookie = Self::new(name, value.trim());
+
+        for attribute in parts {
+            let (name, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let value = value.trim();
+
+            match name.trim().to_ascii_lowercase().as_str() {
+                "domain" if !value.is_empty() => cookie.set_domain(value),
+                "path" if value.starts_with('/') => cookie.set_path(value),
+                "expires" => {
+                    if let Some(expires) = parse_http_date(value) {
+                        cookie.set_expires(expires);
+                    }
+                }
+                // A negative `Max-Age` makes the cookie expire immediately, just like zero
+                "max-age" => {
+                    if let Ok(max_age) = value.parse::<i64>() {
+                        cookie.set_max_age(max_age.max(0) as u64);
+                    }
+                }
+                "secure" => cookie.set_secure(true),
+                "httponly" => cookie.set_http_only(true),
+                "samesite" => {
+                    let same_site = match value.to_ascii_lowercase().as_str() {
+                        "strict" => SameSite::Strict,
+                        "lax" => SameSite::Lax,
+                        "none" => SameSite::None,
+                        _ => continue,
+                    };
+                    cookie.set_same_site(same_site);
+                }
+                _ => (),
+            }
+        }
+
+        Some(cookie)
+    }
+
+    /// Set the `Domain` attribute, which specifies the hosts the cookie will be sent to
+    pub fn set_domain<S>(&mut self, domain: S)
+    where
+        S: Into<String>,
+    {
+        self.domain = Some(domain.into());
+    }
+
+    /// Set the `Path` attribute, which limits the cookie to a specific path and its subdirectories
+    pub fn set_path<S>(&mut self, path: S)
+    where
+        S: Into<String>,
+    {
+        self.path = Some(path.into());
+    }
+
+    /// Set the `Expires` attribute, which specifies the time the cookie expires
+    pub fn set_expires(&mut self, expires: SystemTime) {
+        self.expires = Some(expires);
+    }
+
+    /// Set the `Max-Age` attribute, which specifies the number of seconds until the cookie expires. It takes precedence over the `Expires` attribute
+    pub fn set_max_age(&mut self, max_age: u64) {
+        self.max_age = Some(max_age);
+    }
+
+    /// Set the `HttpOnly` attribute, which forbids scripts from accessing the cookie
+    pub fn set_http_only(&mut self, http_only: bool) {
+        self.http_only = http_only;
+    }
+
+    /// Set the `Secure` attribute, which makes the client send the cookie only over secure connections
+    ///
+    /// A cookie with the `SameSite=None` attribute must be secure, so the attribute can't be removed from it
+    pub fn set_secure(&mut self, secure: bool) {
+        self.secure = secure || self.same_site == Some(SameSite::None);
+    }
+
+    /// Set the `SameSite` attribute, which controls whether the cookie is sent along with cross-site requests
+    ///
+    /// Since clients reject `SameSite=None` cookies that aren't secure, setting it also sets the `Secure` attribute
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use oak_http_server::{Cookie, SameSite};
+    ///
+    /// fn main() {
+    ///     let mut cookie = Cookie::new("tracking", "off");
+    ///     cookie.set_same_site(SameSite::None);
+    ///     assert_eq!(cookie.to_string(), "tracking=off; Secure; SameSite=None");
+    ///
+    ///     // The `Secure` attribute is kept as long as the cookie is `SameSite=None`
+    ///     cookie.set_secure(false);
+    ///     assert_eq!(cookie.to_string(), "tracking=off; Secure; SameSite=None");
+    ///
+    ///     cookie.set_same_site(SameSite::Lax);
+    ///     cookie.set_secure(false);
+    ///     assert_eq!(cookie.to_string(), "tracking=off; SameSite=Lax");
+    /// }
+    /// ```
+    pub fn set_same_site(&mut self, same_site: SameSite) {
+        if same_site == SameSite::None {
+            self.secure = true;
+        }
+        self.same_site = Some(same_site);
+    }
+}
+
+impl fmt::Display for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={}", domain)?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, "; Path={}", path)?;
+        }
+        if let Some(expires) = self.expires {
+            write!(f, "; Expires={}", format_time(expires))?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age)?;
+        }
+        if self.secure {
+            write!(f, "; Secure")?;
+        }
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+        if let Some(same_site) = &self.same_site {
+            write!(f, "; SameSite={}", same_site)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Cookie values often contain session identifiers, so keep them out of logs
+        f.debug_struct("Cookie")
+            .field("name", &self.name)
+            .field("value", &"<redacted>")
+            .field("domain", &self.domain)
+            .field("path", &self.path)
+            .field("expires", &self.expires)
+            .field("max_age", &self.max_age)
+            .field("http_only", &self.http_only)
+            .field("secure", &self.secure)
+            .field("same_site", &self.same_site)
+            .finish()
+    }
+}
+
+/// The identifier of a node (a client or a proxy) in a `Forwarded` header (RFC 7239 §6)
+#[derive(PartialEq, Clone, Debug)]
+pub enum NodeName {
+    /// An IPv4 or IPv6 address
+    Address(IpAddr),
+    /// An obfuscated identifier, starting with an underscore, which is used to hide the real address of a node
+    Obfuscated(String),
+    /// The node is unknown, for example because the proxy doesn't want to reveal it
+    Unknown,
+}
+
+/// A node in a `Forwarded` header, consisting of a [`NodeName`] and an optional port
+#[derive(PartialEq, Clone, Debug)]
+pub struct ForwardedNode {
+    /// The name of the node
+    pub name: NodeName,
+    /// The port of the node (if specified). It is a [`String`], since ports may be obfuscated as well
+    pub port: Option<String>,
+}
+
+impl ForwardedNode {
+    /// Parse a node identifier, such as `192.0.2.60`, `"[2001:db8:cafe::17]:4711"` (after removing the quotes) or `_hidden`
+    fn new(node: &str) -> Option<Self> {
+        // IPv6 addresses are enclosed in brackets, since they contain colons themselves
+        let (name, port) = if let Some(rest) = node.strip_prefix('[') {
+            let (address, rest) = rest.split_once(']')?;
+            let port = match rest {
+                "" => None,
+                _ => Some(rest.strip_prefix(':')?),
+            };
+
+            (NodeName::Address(IpAddr::V6(address.parse().ok()?)), port)
+        } else {
+            let (name, port) = match node.split_once(':') {
+                Some((name, port)) => (name, Some(port)),
+                None => (node, None),
+            };
+
+            let name = if name.eq_ignore_ascii_case("unknown") {
+                NodeName::Unknown
+            } else if name.starts_with('_') {
+                NodeName::Obfuscated(name.to_string())
+            } else {
+                NodeName::Address(IpAddr::V4(name.parse().ok()?))
+            };
+
+            (name, port)
+        };
+
+        if port.is_some_and(|port| port.is_empty()) {
+            return None;
+        }
+
+        Some(Self {
+            name,
+            port: port.map(str::to_string),
+        })
+    }
+}
+
+/// A single element of a `Forwarded` header, containing the information one proxy added about a request (RFC 7239)
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct ForwardedElement {
+    /// The `by` parameter: the interface of the proxy where the request came in
+    pub by: Option<ForwardedNode>,
+    /// The `for` parameter: the client (or previous proxy) that made the request to the proxy
+    pub forwarded_for: Option<ForwardedNode>,
+    /// The `host` parameter: the `Host` header of the request, as received by the proxy
+    pub host: Option<String>,
+    /// The `proto` parameter: the protocol used to make the request (e.g. `http` or `https`)
+    pub proto: Option<String>,
+}
+
+impl ForwardedElement {
+    /// Parse the value of a `Forwarded` header into a list of [`ForwardedElement`]s, in the order the proxies added them
+    ///
+    /// Parameter values may be tokens or quoted strings. Malformed elements and unknown parameters are skipped
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use oak_http_server::{ForwardedElement, NodeName};
+    /// use std::net::{IpAddr, Ipv6Addr};
+    ///
+    /// fn main() {
+    ///     let elements = ForwardedElement::parse_all(
+    ///         r#"for="[2001:db8:cafe::17]:4711";proto=https, for=_hidden;by=unknown"#,
+    ///     );
+    ///
+    ///     let client = elements[0].forwarded_for.as_ref().unwrap();
+    ///     assert_eq!(
+    ///         client.name,
+    ///         NodeName::Address(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0xcafe, 0, 0, 0, 0, 0x17)))
+    ///     );
+    ///     assert_eq!(client.port.as_deref(), Some("4711"));
+    ///     assert_eq!(elements[0].proto.as_deref(), Some("https"));
+    ///
+    ///     assert_eq!(
+    ///         elements[1].forwarded_for.as_ref().unwrap().name,
+    ///         NodeName::Obfuscated(String::from("_hidden"))
+    ///     );
+    ///     assert_eq!(elements[1].by.as_ref().unwrap().name, NodeName::Unknown);
+    /// }
+    /// ```
+    pub fn parse_all<S>(value: S) -> Vec<Self>
+    where
+        S: Into<String>,
+    {
+        let value: String = value.into();
+        let mut elements = Vec::new();
+
+        // Elements are separated by commas and pairs by semicolons, but both may also appear inside quoted strings
+        let mut element = Self::default();
+        let mut valid = true;
+        let mut characters = value.chars().peekable();
+
+        loop {
+            // Skip whitespace before each pair
+            while characters
+                .peek()
+                .is_some_and(|character| *character == ' ' || *character == '\t')
+            {
+                characters.next();
+            }
+
+            let name: String = characters
+                .by_ref()
+                .take_while(|character| *character != '=')
+                .collect();
+
+            let mut value = String::new();
+            let mut terminator = None;
+
+            if characters.peek() == Some(&'"') {
+                characters.next();
+
+                let mut closed = false;
+                while let Some(character) = characters.next() {
+                    match character {
+                        '\\' => value.extend(characters.next()),
+                        '"' => {
+                            closed = true;
+                            break;
+                        }
+                        _ => value.push(character),
+                    }
+                }
+                valid &= closed;
+
+                // Only whitespace may follow the closing quote
+                for character in characters.by_ref() {
+                    if character == ';' || character == ',' {
+                        terminator = Some(character);
+                        break;
+                    }
+                    valid &= character == ' ' || character == '\t';
+                }
+            } else {
+                for character in characters.by_ref() {
+                    if character == ';' || character == ',' {
+                        terminator = Some(character);
+                        break;
+                    }
+                    value.push(character);
+                }
+                value = value.trim_end().to_string();
+            }
+
+            let name = name.trim();
+            if !name.is_empty() {
+                match name.to_ascii_lowercase().as_str() {
+                    "by" => element.by = ForwardedNode::new(&value),
+                    "for" => element.forwarded_for = ForwardedNode::new(&value),
+                    "host" => element.host = Some(value),
+                    "proto" => element.proto = Some(value.to_ascii_lowercase()),
+                    _ => (),
+                }
+            }
+
+            if terminator != Some(';') {
+                if valid && element != Self::default() {
+                    elements.push(element);
+                }
+                element = Self::default();
+                valid = true;
+            }
+
+            if terminator.is_none() {
+                break;
+            }
+        }
+
+        elements
+    }
+}
+
+/// The time limits the server enforces on each phase of a connection
+///
+/// A limit of [`None`] disables the corresponding timeout. Unspecified limits can be filled in with their defaults using [`Default`]
+///
+/// # Example
+///
+/// ```
+/// use oak_http_server::{Server, Timeouts};
+/// use std::time::Duration;
+///
+/// fn main() {
+///     let mut server = Server::new("localhost", 2300 as u16);
+///
+///     // Allow slow uploads, but keep the other defaults
+///     server.timeouts(Timeouts {
+///         body_read: Some(Duration::from_secs(600)),
+///         ..Default::default()
+///     });
+/// }
+/// ```
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Timeouts {
+    /// The maximum time to receive the request line and the headers of a request, counted from the arrival of its first byte (30 seconds by default)
+    ///
+    /// On expiry, the server responds with a `408 Request Timeout` status and closes the connection
+    pub header_read: Option<Duration>,
+    /// The maximum time to receive the body of a request (60 seconds by default)
+    ///
+    /// On expiry, the server responds with a `408 Request Timeout` status and closes the connection
+    pub body_read: Option<Duration>,
+    /// The maximum time a handler may take to respond (60 seconds by default)
+    ///
+    /// On expiry, the server responds with a `503 Service Unavailable` status and closes the connection. Only the asynchronous server can interrupt a handler, so the synchronous [`Server`](crate::Server) ignores this limit
+    pub handler: Option<Duration>,
+    /// The maximum time to wait for a request to begin, either on a new connection or between requests on a kept-alive one (5 seconds by default)
+    ///
+    /// On expiry, the connection is closed without a response
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use oak_http_server::{Server, Timeouts};
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpStream;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// fn main() {
+    ///     let mut server = Server::new("localhost", 0 as u16);
+    ///     server.timeouts(Timeouts {
+    ///         keep_alive_idle: Some(Duration::from_millis(200)),
+    ///         ..Default::default()
+    ///     });
+    ///     server.on_get("/", |_request, response| response.send("hello"));
+    ///
+    ///     let handle = server.start_with_handle().unwrap();
+    ///
+    ///     // A client that never sends a request is disconnected without a response
+    ///     let mut stream = TcpStream::connect(handle.local_address()).unwrap();
+    ///     let start = Instant::now();
+    ///     let mut received = Vec::new();
+    ///     stream.read_to_end(&mut received).unwrap();
+    ///     assert!(received.is_empty());
+    ///     assert!(start.elapsed() >= Duration::from_millis(200));
+    ///
+    ///     // The same applies to a kept-alive connection once a response has been sent
+    ///     let mut stream = TcpStream::connect(handle.local_address()).unwrap();
+    ///     stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    ///     let mut received = String::new();
+    ///     stream.read_to_string(&mut received).unwrap();
+    ///     assert!(received.starts_with("HTTP/1.1 200"));
+    ///     assert!(received.ends_with("hello"));
+    ///
+    ///     handle.stop();
+    /// }
+    /// ```
+    pub keep_alive_idle: Option<Duration>,
+    /// The maximum time sending a response to the client may be blocked for (30 seconds by default)
+    ///
+    /// On expiry, the connection is closed
+    pub write: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            header_read: Some(Duration::from_secs(30)),
+            body_read: Some(Duration::from_secs(60)),
+            handler: Some(Duration::from_secs(60)),
+            keep_alive_idle: Some(Duration::from_secs(5)),
+            write: Some(Duration::from_secs(30)),
+        }
+    }
+}
+
+/// The Cross-Origin Resource Sharing (CORS) policy of a [`Server`](crate::Server), set with [`Server::with_cors()`](crate::Server::with_cors())
+///
+/// Unspecified fields can be filled in with their defaults using [`Default`], which allow `GET`, `HEAD` and `POST` requests from any origin
+///
+/// # Example
+///
+/// ```
+/// use oak_http_server::{CorsConfig, Method, Server};
+///
+/// fn main() {
+///     let mut server = Server::new("localhost", 2300 as u16);
+///
+///     server.with_cors(CorsConfig {
+///         allowed_origins: vec![String::from("https://example.com")],
+///         allowed_methods: vec![Method::GET, Method::PUT],
+///         allowed_headers: vec![String::from("Content-Type")],
+///         ..Default::default()
+///     });
+/// }
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct CorsConfig {
+    /// The origins allowed to make cross-origin requests, such as `https://example.com`. An origin of `*` allows any origin (the default)
+    pub allowed_origins: Vec<String>,
+    /// The methods allowed in cross-origin requests (`GET`, `HEAD` and `POST` by default)
+    pub allowed_methods: Vec<Method>,
+    /// The request headers allowed in cross-origin requests, besides the ones browsers always allow (none by default)
+    pub allowed_headers: Vec<String>,
+    /// Whether cross-origin requests may include credentials, such as cookies (disabled by default)
+    ///
+    /// Browsers don't accept credentials from any origin, so the origin of the request is sent back instead of `*`
+    pub allow_credentials: bool,
+    /// For how long browsers may cache the response to a preflight request (not sent by default, in which case browsers use their own default)
+    pub max_age: Option<Duration>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![String::from("*")],
+            allowed_methods: vec![Method::GET, Method::HEAD, Method::POST],
+            allowed_headers: Vec::new(),
+            allow_credentials: false,
+            max_age: None,
+        }
+    }
+}
+
+impl CorsConfig {
+    /// Whether a request is a CORS preflight request, which asks whether the actual request is allowed before it is sent
+    pub(crate) fn is_preflight(method: &Method, headers: &Headers) -> bool {
+        *method == Method::OPTIONS
+            && get_header(headers, "Origin").is_some()
+            && get_header(headers, "Access-Control-Request-Method").is_some()
+    }
+
+    /// Add the CORS headers of a response to an actual request, if it was sent from an allowed origin
+    pub(crate) fn apply(&self, request_headers: &Headers, response_headers: &mut Headers) {
+        if let Some(origin) = get_header(request_headers, "Origin") {
+            self.allow_origin(origin, response_headers);
+        }
+    }
+
+    /// Add the CORS headers of a response to a preflight request, if the actual request is allowed
+    pub(crate) fn preflight(&self, request_headers: &Headers, response_headers: &mut Headers) {
+        let Some(origin) = get_header(request_headers, "Origin") else {
+            return;
+        };
+
+        let method_allowed = get_header(request_headers, "Access-Control-Request-Method")
+            .and_then(|method| Method::new(method.trim()))
+            .is_some_and(|method| self.allowed_methods.contains(&method));
+        let headers_allowed = get_header(request_headers, "Access-Control-Request-Headers")
+            .is_none_or(|names| {
+                names
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .all(|name| {
+                        self.allowed_headers
+                            .iter()
+                            .any(|allowed| allowed.eq_ignore_ascii_case(name))
+                    })
+            });
+
+        // Without the CORS headers, the browser won't send the actual request
+        if !method_allowed || !headers_allowed || !self.allow_origin(origin, response_headers) {
+            return;
+        }
+
+        let methods: Vec<String> = self.allowed_methods.iter().map(Method::to_string).collect();
+        response_headers.insert(
+            String::from("Access-Control-Allow-Methods"),
+            methods.join(", "),
+        );
+        if !self.allowed_headers.is_empty() {
+            response_headers.insert(
+                String::from("Access-Control-Allow-Headers"),
+                self.allowed_headers.join(", "),
+            );
+        }
+        if let Some(max_age) = self.max_age {
+            response_headers.insert(
+                String::from("Access-Control-Max-Age"),
+                max_age.as_secs().to_string(),
+            );
+        }
+    }
+
+    /// Add the `Access-Control-Allow-Origin` header (and `Access-Control-Allow-Credentials`, if enabled) to a response, returning whether the origin is allowed
+    fn allow_origin(&self, origin: &str, response_headers: &mut Headers) -> bool {
+        let any_origin = self.allowed_origins.iter().any(|allowed| allowed == "*");
+
+        // The header depends on the origin of the request, unless it is always `*`
+        if !any_origin || self.allow_credentials {
+            add_vary(response_headers, "Origin");
+        }
+
+        let allowed_origin = if any_origin && !self.allow_credentials {
+            String::from("*")
+        } else if any_origin
+            || self
+                .allowed_origins
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(origin))
+        {
+            origin.to_string()
+        } else {
+            return false;
+        };
+
+        response_headers.insert(String::from("Access-Control-Allow-Origin"), allowed_origin);
+        if self.allow_credentials {
+            response_headers.insert(
+                String::from("Access-Control-Allow-Credentials"),
+                String::from("true"),
+            );
+        }
+
+        true
+    }
+}
+
+/// A summary of a completed request and the response sent to it, passed to the hooks registered with [`Server::on_response()`](crate::Server::on_response)
+#[derive(Clone, Debug)]
+pub struct ResponseInfo {
+    /// The address of the client (if known)
+    pub peer_address: Option<SocketAddr>,
+    /// The method of the request
+    pub method: Method,
+    /// The target of the request
+    pub target: Target,
+    /// The HTTP version of the request
+    pub version: Version,
+    /// The status of the response
+    pub status: Status,
+    /// Whether a handler or middleware sent the response itself. It is `false` if the server responded on its behalf, for example with a `404 Not Found` status
+    /// because no handler matched, or with a `500 Internal Server Error` status because the handler returned without responding
+    pub responded: bool,
+    /// The number of bytes written to the client for the response, including its head
+    pub bytes_written: u64,
+    /// The number of bytes of the response's body, as written to the client
+    pub body_size: u64,
+    /// The time the request was read at
+    pub received: SystemTime,
+    /// The time from the moment the request was read until the response was complete
+    pub duration: Duration,
+}
+
+impl ResponseInfo {
+    /// Format the request and its response as a line in the Common Log Format, as written by [`Server::access_log()`](crate::Server::access_log)
+    ///
+    /// The identity and the user of the client are always unknown, so they are written as `-`, as is the size of an empty body
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use oak_http_server::{Method, ResponseInfo, Status, Target, Version};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// fn main() {
+    ///     let info = ResponseInfo {
+    ///         peer_address: Some("127.0.0.1:41630".parse().unwrap()),
+    ///         method: Method::GET,
+    ///         target: Target::new("/apache_pb.gif"),
+    ///         version: Version::new("HTTP/1.0").unwrap(),
+    ///         status: Status::OK,
+    ///         responded: true,
+    ///         bytes_written: 2458,
+    ///         body_size: 2326,
+    ///         received: UNIX_EPOCH + Duration::from_secs(971186136),
+    ///         duration: Duration::from_millis(3),
+    ///     };
+    ///
+    ///     assert_eq!(
+    ///         info.common_log_line(),
+    ///         "127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] \"GET /apache_pb.gif HTTP/1.0\" 200 2326"
+    ///     );
+    /// }
+    /// ```
+    pub fn common_log_line(&self) -> String {
+        format!(
+            "{} - - [{}] \"{} {} {}\" {} {}",
+            self.peer_address
+                .map_or(String::from("-"), |address| address.ip().to_string()),
+            format_log_time(self.received),
+            self.method,
+            self.target,
+            self.version,
+            self.status,
+            match self.body_size {
+                0 => String::from("-"),
+                size => size.to_string(),
+            }
+        )
+    }
+}
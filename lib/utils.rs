@@ -1,32 +1,173 @@
 #![allow(dead_code)]
 
+use std::any::Any;
 use std::collections::HashMap;
-use std::io::Read;
-use std::net::TcpStream;
+use std::io::{self, BufRead, Read, Write};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "signed-cookies")]
+use hmac::{Hmac, Mac};
+#[cfg(feature = "signed-cookies")]
+use sha2::Sha256;
+
+use crate::{Cookie, ReadWrite, Status, Version};
 
 pub type Headers = HashMap<String, String>;
 
-pub fn read_line(stream: &mut TcpStream) -> String {
+/// The maximum length of a request line, header field line or chunk size line
+pub const MAX_LINE_LENGTH: usize = 8 * 1024;
+
+/// Remove the brackets around an IPv6 address literal (e.g. `[::1]`), which can't be resolved with them
+pub fn unbracket_host(hostname: &str) -> &str {
+    hostname
+        .strip_prefix('[')
+        .and_then(|hostname| hostname.strip_suffix(']'))
+        .unwrap_or(hostname)
+}
+
+/// Check whether the value of a `Host` header matches a pattern, ignoring case and any port
+///
+/// A pattern starting with `*.` matches any subdomain of the domain that follows, while a single `*` matches any host
+pub fn host_matches(host: &str, pattern: &str) -> bool {
+    // Strip the port, taking care not to split an IPv6 address
+    let host = match host.rsplit_once(':') {
+        Some((name, port))
+            if !name.is_empty()
+                && (!name.contains(':') || name.ends_with(']'))
+                && port.bytes().all(|byte| byte.is_ascii_digit()) =>
+        {
+            name
+        }
+        _ => host,
+    }
+    .to_ascii_lowercase();
+    let pattern = pattern.to_ascii_lowercase();
+
+    match pattern.strip_prefix('*') {
+        Some("") => true,
+        Some(suffix) if suffix.starts_with('.') => {
+            host.len() > suffix.len() && host.ends_with(suffix)
+        }
+        _ => host == pattern,
+    }
+}
+
+/// Returns the HTTP version a response to a request of the given version should have, or [`None`] if the version isn't supported (only HTTP/1.0 and HTTP/1.1 are)
+pub fn response_version(request_version: &Version) -> Option<Version> {
+    match (request_version.major, request_version.minor) {
+        (1, 0) | (1, 1) => Some(request_version.clone()),
+        _ => None,
+    }
+}
+
+/// Format the status line and the header section of a response. If the length of the body isn't known in advance, the body must be sent using the chunked transfer coding
+pub fn format_head<'c, I>(
+    version: &Version,
+    status: &Status,
+    reason: &str,
+    content_length: Option<u64>,
+    headers: &Headers,
+    cookies: I,
+    title_case_headers: bool,
+) -> String
+where
+    I: Iterator<Item = &'c Cookie>,
+{
+    // Begin with the status line
+    let mut head = format!("{} {} {}\r\n", version, status, reason);
+
+    // Add a header indicating message length, or how the message will be delimited if the length is unknown.
+    // A `304 Not Modified` response never has a body, so its headers describe the representation the client already has instead,
+    // while after a `101 Switching Protocols` response the connection no longer carries HTTP messages
+    // HTTP/1.0 clients don't understand the chunked transfer coding, so a body of unknown length is delimited by closing the connection instead
+    match content_length {
+        _ if *status == Status::NotModified || *status == Status::SwitchingProtocols => (),
+        Some(content_length) => head.push_str(&format!("Content-Length: {}\r\n", content_length)),
+        None if (version.major, version.minor) < (1, 1) => (),
+        None => head.push_str("Transfer-Encoding: chunked\r\n"),
+    }
+
+    for (name, value) in headers {
+        // The framing of the body is decided above, so any conflicting header set by a handler is left out
+        if name.eq_ignore_ascii_case("Content-Length")
+            || name.eq_ignore_ascii_case("Transfer-Encoding")
+        {
+            continue;
+        }
+
+        let name = if title_case_headers {
+            title_case_header(name)
+        } else {
+            name.clone()
+        };
+
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+
+    // Add each cookie in a separate header, since their values can't be combined
+    for cookie in cookies {
+        head.push_str(&format!("Set-Cookie: {}\r\n", cookie));
+    }
+
+    // End with a CRLF to signal the beginning of the message body
+    head.push_str("\r\n");
+
+    head
+}
+
+/// Write an informational (1xx) interim response with the given headers to a stream
+pub fn write_informational<W: Write>(
+    stream: &mut W,
+    status: u16,
+    headers: &[(&str, &str)],
+) -> io::Result<()> {
+    let reason = match status {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        103 => "Early Hints",
+        _ => "",
+    };
+    let mut head = format!("HTTP/1.1 {} {}\r\n", status, reason);
+
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+
+    stream.write_all(head.as_bytes())?;
+    stream.flush()
+}
+
+/// Read a line terminated by a CRLF from a stream, returning [`None`] if the stream ends, can't be read from or the line is too long
+pub fn read_line<R: Read>(stream: &mut R) -> Option<String> {
     let mut temp_string = String::new();
 
     loop {
         let mut temp_array: [u8; 1] = [0];
 
-        if stream.read(&mut temp_array).is_ok() {
-            let temp_char = char::from_u32(temp_array[0] as u32).unwrap();
+        match stream.read(&mut temp_array) {
+            Ok(0) => return None,
+            Ok(_) => {
+                let temp_char = char::from(temp_array[0]);
 
-            if temp_char == '\n' {
-                if temp_string.chars().last().unwrap() == '\r' {
+                if temp_char == '\n' && temp_string.ends_with('\r') {
                     temp_string.pop();
                     break;
                 }
-            }
 
-            temp_string.push(temp_char);
+                // Don't let the client exhaust the server's memory with a never-ending line
+                if temp_string.len() >= MAX_LINE_LENGTH {
+                    return None;
+                }
+
+                temp_string.push(temp_char);
+            }
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => (),
+            Err(_) => return None,
         }
     }
 
-    temp_string
+    Some(temp_string)
 }
 
 pub fn parse_headers<S>(headers: S) -> Headers
@@ -36,30 +177,1426 @@ where
     let headers: String = headers.into();
     let mut temp_hashmap: Headers = HashMap::new();
 
+    // Lines that aren't header fields are skipped
     for header in headers.split("\r\n") {
-        if let Some((name, mut value)) = header.split_once(":") {
-            // Trim the value str from any whitespaces
-            value = value.trim();
-            temp_hashmap.insert(name.to_string(), value.to_string());
-        }
+        let _ = parse_header_line(&mut temp_hashmap, header);
     }
 
     temp_hashmap
 }
 
+/// Header fields whose values can't be combined into a comma-separated list, since they may contain commas themselves (RFC 9110 §5.3).
+/// If one of them is repeated, only its last value is kept
+const UNCOMBINABLE_HEADERS: [&str; 1] = ["Set-Cookie"];
+
+/// Returns whether a string is a non-empty token (RFC 9110 §5.6.2), the syntax of header names and methods
+pub fn is_token(string: &str) -> bool {
+    !string.is_empty()
+        && string
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+/// Parse a header field line and add it to `headers`, returning [`None`] if it isn't a valid header field
+///
+/// The name of the field must be a token, without any whitespace before the colon (RFC 9112 §5.1). Otherwise, a request could carry a field,
+/// such as `Content-Length : 5`, that a proxy in front of the server interprets differently
+///
+/// A repeated header field is combined with the existing one by appending its value after a `, `, with the exception of `Cookie`, whose values are separated by a `; `
+/// (RFC 9113 §8.2.3), and the fields in [`UNCOMBINABLE_HEADERS`]
 pub fn parse_header_line<S>(headers: &mut Headers, line: S) -> Option<()>
 where
     S: Into<String>,
 {
     let header: String = line.into();
+    let (name, value) = header.split_once(':')?;
+    if !is_token(name) {
+        return None;
+    }
+
+    // Trim the value str from any whitespaces
+    let mut name = name.to_string();
+    let mut value = value.trim().to_string();
+
+    // Header names are case-insensitive, so a repeated field may be spelled differently than the existing one
+    if let Some(existing) = headers
+        .keys()
+        .find(|existing| existing.eq_ignore_ascii_case(&name))
+        .cloned()
+    {
+        let previous = headers.remove(&existing).unwrap_or_default();
+
+        if !UNCOMBINABLE_HEADERS
+            .iter()
+            .any(|uncombinable| uncombinable.eq_ignore_ascii_case(&name))
+        {
+            let separator = if name.eq_ignore_ascii_case("Cookie") {
+                "; "
+            } else {
+                ", "
+            };
+            value = format!("{}{}{}", previous, separator, value);
+            name = existing;
+        }
+    }
+
+    headers.insert(name, value);
+
+    Some(())
+}
+
+/// Wrap a string in double quotes, escaping any characters that aren't allowed inside a quoted-string (RFC 9110 §5.6.4)
+///
+/// Non-ASCII and control characters can't be represented, so they are replaced with an underscore
+pub fn quote_string(string: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for character in string.chars() {
+        match character {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(character);
+            }
+            ' '..='~' => quoted.push(character),
+            _ => quoted.push('_'),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// Percent-encode a string into an `ext-value` using the UTF-8 charset (RFC 8187 §3.2)
+pub fn encode_ext_value(value: &str) -> String {
+    let encoded = percent_encode(value, |byte| {
+        byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte)
+    });
+
+    format!("UTF-8''{}", encoded)
+}
+
+/// Format the value of a `Content-Disposition` header with the specified disposition type and an optional filename
+///
+/// If the filename contains non-ASCII characters, an ASCII fallback is provided in the `filename` parameter and the original name in the `filename*` parameter
+pub fn content_disposition(disposition: &str, filename: Option<&str>) -> String {
+    match filename {
+        Some(filename) if filename.is_ascii() => {
+            format!("{}; filename={}", disposition, quote_string(filename))
+        }
+        Some(filename) => format!(
+            "{}; filename={}; filename*={}",
+            disposition,
+            quote_string(filename),
+            encode_ext_value(filename)
+        ),
+        None => disposition.to_string(),
+    }
+}
+
+/// Parse a HTTP date (e.g. the value of a `Date` or `If-Modified-Since` header) into a [`SystemTime`]
+///
+/// As required by RFC 9110 §5.6.7, the preferred IMF-fixdate format is accepted along with the two obsolete formats, RFC 850 and ANSI C's `asctime()`. Returns [`None`] if the date isn't in any of these formats or is before the UNIX epoch
+///
+/// # Example
+///
+/// ```
+/// # use oak_http_server::parse_http_date;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// fn main() {
+///     let expected = Some(UNIX_EPOCH + Duration::from_secs(784111777));
+///
+///     // IMF-fixdate
+///     assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), expected);
+///     // RFC 850
+///     assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), expected);
+///     // asctime
+///     assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), expected);
+///
+///     assert_eq!(parse_http_date("Sun, 31 Feb 1994 08:49:37 GMT"), None);
+///     assert_eq!(parse_http_date("yesterday"), None);
+/// }
+/// ```
+pub fn parse_http_date(date: &str) -> Option<SystemTime> {
+    parse_imf_fixdate(date)
+        .or_else(|| parse_rfc850_date(date))
+        .or_else(|| parse_asctime_date(date))
+}
+
+/// Format a [`SystemTime`] as a HTTP date in the IMF-fixdate format (e.g. `Sun, 06 Nov 1994 08:49:37 GMT`)
+///
+/// This is the inverse of [`parse_http_date`]. Times before the UNIX epoch are formatted as the epoch itself
+///
+/// # Example
+///
+/// ```
+/// # use oak_http_server::format_time;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// fn main() {
+///     let time = UNIX_EPOCH + Duration::from_secs(784111777);
+///
+///     assert_eq!(format_time(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+/// }
+/// ```
+pub fn format_time(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs();
+    let days = seconds / 86_400;
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        // The UNIX epoch was a Thursday
+        DAY_NAMES[((days + 3) % 7) as usize],
+        day,
+        MONTH_NAMES[month as usize - 1],
+        year,
+        seconds % 86_400 / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    )
+}
+
+/// Remove the characters that aren't allowed in the reason phrase of a status line, which are the control characters other than tabs
+pub fn reason_phrase(text: String) -> String {
+    text.chars()
+        .filter(|character| *character == '\t' || !character.is_control())
+        .collect()
+}
+
+/// Format a [`SystemTime`] as in the Common Log Format (e.g. `06/Nov/1994:08:49:37 +0000`)
+pub fn format_log_time(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs();
+    let (year, month, day) = civil_from_days(seconds / 86_400);
+
+    format!(
+        "{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+        day,
+        MONTH_NAMES[month as usize - 1],
+        year,
+        seconds % 86_400 / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    )
+}
+
+/// Parse a date in the IMF-fixdate format (e.g. `Sun, 06 Nov 1994 08:49:37 GMT`)
+fn parse_imf_fixdate(date: &str) -> Option<SystemTime> {
+    let mut parts = date.split_whitespace();
+
+    let day_name = parts.next()?.strip_suffix(',')?;
+    if !DAY_NAMES.contains(&day_name) {
+        return None;
+    }
+
+    let day = parse_number(parts.next()?, 2)?;
+    let month = parse_month(parts.next()?)?;
+    let year = parse_number(parts.next()?, 4)?;
+    let (hours, minutes, seconds) = parse_time_of_day(parts.next()?)?;
+
+    if parts.next()? != "GMT" || parts.next().is_some() {
+        return None;
+    }
+
+    to_system_time(year, month, day, hours, minutes, seconds)
+}
+
+/// Parse a date in the obsolete RFC 850 format (e.g. `Sunday, 06-Nov-94 08:49:37 GMT`)
+fn parse_rfc850_date(date: &str) -> Option<SystemTime> {
+    let mut parts = date.split_whitespace();
+
+    let day_name = parts.next()?.strip_suffix(',')?;
+    if !LONG_DAY_NAMES.contains(&day_name) {
+        return None;
+    }
+
+    let mut date_parts = parts.next()?.split('-');
+    let day = parse_number(date_parts.next()?, 2)?;
+    let month = parse_month(date_parts.next()?)?;
+    let short_year = parse_number(date_parts.next()?, 2)?;
+    if date_parts.next().is_some() {
+        return None;
+    }
+
+    let (hours, minutes, seconds) = parse_time_of_day(parts.next()?)?;
+
+    if parts.next()? != "GMT" || parts.next().is_some() {
+        return None;
+    }
+
+    // A two-digit year that appears to be more than 50 years in the future is interpreted as the most recent past year with the same last two digits (RFC 9110 §5.6.7)
+    let current_year = civil_from_days(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+            .as_secs()
+            / 86_400,
+    )
+    .0;
+    let mut year = current_year - current_year % 100 + short_year;
+    if year > current_year + 50 {
+        year -= 100;
+    }
+
+    to_system_time(year, month, day, hours, minutes, seconds)
+}
+
+/// Parse a date in ANSI C's `asctime()` format (e.g. `Sun Nov  6 08:49:37 1994`)
+fn parse_asctime_date(date: &str) -> Option<SystemTime> {
+    let mut parts = date.split_whitespace();
+
+    if !DAY_NAMES.contains(&parts.next()?) {
+        return None;
+    }
+
+    let month = parse_month(parts.next()?)?;
+    // The day is padded with a space instead of a zero, so it may consist of a single digit
+    let day_string = parts.next()?;
+    let day = parse_number(day_string, day_string.len().clamp(1, 2))?;
+    let (hours, minutes, seconds) = parse_time_of_day(parts.next()?)?;
+    let year = parse_number(parts.next()?, 4)?;
 
-    if let Some((name, mut value)) = header.split_once(":") {
-        // Trim the value str from any whitespaces
-        value = value.trim();
-        headers.insert(name.to_string(), value.to_string());
+    if parts.next().is_some() {
+        return None;
+    }
+
+    to_system_time(year, month, day, hours, minutes, seconds)
+}
+
+const DAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const LONG_DAY_NAMES: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
 
-        Some(())
+/// Parse a number that consists of exactly `digits` ASCII digits
+fn parse_number(string: &str, digits: usize) -> Option<u64> {
+    if string.len() != digits || !string.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    string.parse().ok()
+}
+
+/// Convert a three-letter month name into its number (starting from 1)
+fn parse_month(month: &str) -> Option<u64> {
+    MONTH_NAMES
+        .iter()
+        .position(|&name| name == month)
+        .map(|index| index as u64 + 1)
+}
+
+/// Parse a time of day in the format `HH:MM:SS`
+fn parse_time_of_day(time: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = time.split(':');
+
+    let hours = parse_number(parts.next()?, 2)?;
+    let minutes = parse_number(parts.next()?, 2)?;
+    let seconds = parse_number(parts.next()?, 2)?;
+
+    if parts.next().is_some() || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    Some((hours, minutes, seconds))
+}
+
+/// Convert a UTC date and time into a [`SystemTime`]. Dates before the UNIX epoch aren't supported
+fn to_system_time(
+    year: u64,
+    month: u64,
+    day: u64,
+    hours: u64,
+    minutes: u64,
+    seconds: u64,
+) -> Option<SystemTime> {
+    let is_leap_year =
+        (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400);
+    let days_in_month = match month {
+        2 if is_leap_year => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+
+    if year < 1970 || day == 0 || day > days_in_month {
+        return None;
+    }
+
+    // Count the days since the epoch, treating March as the first month of the year so that leap days fall at its end
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
     } else {
+        (year, month - 3)
+    };
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let days = year * 365 + year / 4 - year / 100 + year / 400 + day_of_year - 719_468;
+
+    UNIX_EPOCH.checked_add(Duration::from_secs(
+        days * 86_400 + hours * 3_600 + minutes * 60 + seconds,
+    ))
+}
+
+/// Convert a number of days since the UNIX epoch into a `(year, month, day)` tuple
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // Shift the epoch to 0000-03-01, so that leap days fall at the end of each 400-year era and year
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Returns the MIME type corresponding to a file extension, defaulting to `application/octet-stream` for unknown (or missing) extensions
+pub fn mime_type(extension: Option<&str>) -> &'static str {
+    let Some(extension) = extension else {
+        return "application/octet-stream";
+    };
+
+    match extension.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "txt" => "text/plain; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "xml" => "application/xml",
+        "json" => "application/json",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "avif" => "image/avif",
+
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+
+        _ => "application/octet-stream",
+    }
+}
+
+/// The result of evaluating a `Range` header against a representation
+pub enum ByteRange {
+    /// An inclusive range of bytes that can be sent
+    Satisfiable(u64, u64),
+    /// The range doesn't overlap with the representation
+    Unsatisfiable,
+}
+
+/// Parse the value of a `Range` header for a representation of `length` bytes
+///
+/// Only a single byte range is supported. [`None`] is returned for invalid or unsupported values, in which case the header should be ignored (RFC 9110 §14.2)
+pub fn parse_byte_range(value: &str, length: u64) -> Option<ByteRange> {
+    let (unit, range) = value.trim().split_once('=')?;
+    if !unit.eq_ignore_ascii_case("bytes") || range.contains(',') {
+        return None;
+    }
+
+    let (first, last) = range.trim().split_once('-')?;
+    let parse = |number: &str| -> Option<u64> {
+        if number.is_empty() || !number.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        number.parse().ok()
+    };
+
+    if first.is_empty() {
+        // A suffix range, which requests the last N bytes of the representation
+        let suffix_length = parse(last)?;
+        if suffix_length == 0 || length == 0 {
+            return Some(ByteRange::Unsatisfiable);
+        }
+
+        return Some(ByteRange::Satisfiable(
+            length.saturating_sub(suffix_length),
+            length - 1,
+        ));
+    }
+
+    let first = parse(first)?;
+    let last = if last.is_empty() {
         None
+    } else {
+        Some(parse(last)?)
+    };
+
+    if last.is_some_and(|last| last < first) {
+        return None;
+    }
+
+    if first >= length {
+        return Some(ByteRange::Unsatisfiable);
+    }
+
+    Some(ByteRange::Satisfiable(
+        first,
+        last.map_or(length - 1, |last| last.min(length - 1)),
+    ))
+}
+
+/// Header names whose canonical capitalization isn't plain hyphenated title case
+const CANONICAL_HEADER_NAMES: [&str; 13] = [
+    "Content-MD5",
+    "DNT",
+    "ETag",
+    "Sec-WebSocket-Accept",
+    "Sec-WebSocket-Extensions",
+    "Sec-WebSocket-Key",
+    "Sec-WebSocket-Protocol",
+    "Sec-WebSocket-Version",
+    "TE",
+    "WWW-Authenticate",
+    "X-UA-Compatible",
+    "X-XSS-Protection",
+    "X-DNS-Prefetch-Control",
+];
+
+/// Convert a header name to its canonical capitalization (e.g. `content-type` to `Content-Type` and `etag` to `ETag`)
+pub fn title_case_header(name: &str) -> String {
+    if let Some(canonical) = CANONICAL_HEADER_NAMES
+        .iter()
+        .find(|canonical| canonical.eq_ignore_ascii_case(name))
+    {
+        return canonical.to_string();
+    }
+
+    name.split('-')
+        .map(|word| {
+            let mut characters = word.chars();
+            match characters.next() {
+                Some(first) => {
+                    first.to_ascii_uppercase().to_string()
+                        + &characters.as_str().to_ascii_lowercase()
+                }
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+/// Add a header name to the `Vary` header of a response, unless it is already listed
+pub fn add_vary(headers: &mut Headers, name: &str) {
+    match headers
+        .iter_mut()
+        .find(|(header_name, _)| header_name.eq_ignore_ascii_case("Vary"))
+    {
+        Some((_, vary)) => {
+            if !has_token(vary, name) {
+                vary.push_str(", ");
+                vary.push_str(name);
+            }
+        }
+        None => {
+            headers.insert(String::from("Vary"), name.to_string());
+        }
+    }
+}
+
+/// Returns whether a comma-separated header value (such as the value of a `Connection` header) contains a token, regardless of case
+pub fn has_token(value: &str, token: &str) -> bool {
+    value
+        .split(',')
+        .any(|listed| listed.trim().eq_ignore_ascii_case(token))
+}
+
+/// Returns whether the chunked transfer coding is the last one listed in a `Transfer-Encoding` header, which is the only way the end of a request body with that header can be found (RFC 9112 §6.3)
+pub fn is_chunked(transfer_encoding: &str) -> bool {
+    transfer_encoding
+        .rsplit(',')
+        .next()
+        .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+}
+
+/// Find the value of a header, ignoring the case of its name
+pub fn get_header<'h>(headers: &'h Headers, name: &str) -> Option<&'h String> {
+    headers
+        .iter()
+        .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+/// Read exactly `length` bytes from a stream, returning [`None`] if the stream ends prematurely
+pub fn read_bytes<R: Read>(stream: &mut R, length: usize) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    // Don't trust the length to preallocate the buffer, since it is usually provided by the client
+    stream
+        .take(length as u64)
+        .read_to_end(&mut bytes)
+        .ok()
+        .filter(|&read| read == length)?;
+
+    Some(bytes)
+}
+
+/// Returns whether the `Content-Length` header of a request declares a body larger than `max_size` bytes
+pub fn declared_length_exceeds(headers: &Headers, max_size: usize) -> bool {
+    get_header(headers, "Content-Length")
+        .and_then(|length| length.parse::<u64>().ok())
+        .is_some_and(|length| length > max_size as u64)
+}
+
+/// Read the message body of a request with the given headers, along with its trailer fields (if it uses the chunked transfer coding)
+///
+/// On failure, returns the status that should be sent to the client: `400 Bad Request` if the body is malformed, `413 Content Too Large` if it is larger than `max_size` bytes,
+/// or `431 Request Header Fields Too Large` if there are more than `max_trailers` trailer fields or they are larger than `max_trailer_size` bytes in total
+pub fn read_body<R: Read>(
+    stream: &mut R,
+    headers: &Headers,
+    max_size: usize,
+    max_trailers: usize,
+    max_trailer_size: usize,
+) -> Result<(Vec<u8>, Headers), Status> {
+    if get_header(headers, "Transfer-Encoding").is_some_and(|encoding| is_chunked(encoding)) {
+        read_chunked_body(stream, max_size, max_trailers, max_trailer_size)
+    } else if let Some(length) = get_header(headers, "Content-Length") {
+        let length = length.parse::<usize>().map_err(|_| Status::BadRequest)?;
+        if length > max_size {
+            return Err(Status::ContentTooLarge);
+        }
+
+        let body = read_bytes(stream, length).ok_or(Status::BadRequest)?;
+        Ok((body, Headers::new()))
+    } else {
+        Ok((Vec::new(), Headers::new()))
+    }
+}
+
+/// Read a message body encoded with the chunked transfer coding (RFC 9112 §7.1), failing in the same way as [`read_body`]
+pub fn read_chunked_body<R: Read>(
+    stream: &mut R,
+    max_size: usize,
+    max_trailers: usize,
+    max_trailer_size: usize,
+) -> Result<(Vec<u8>, Headers), Status> {
+    let mut body = Vec::new();
+
+    loop {
+        // Each chunk begins with its size in hexadecimal, optionally followed by chunk extensions (which are ignored)
+        let size_line = read_line(stream).ok_or(Status::BadRequest)?;
+        let size_string = size_line.split(';').next().unwrap_or_default().trim();
+        if size_string.is_empty() || !size_string.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(Status::BadRequest);
+        }
+        let size = usize::from_str_radix(size_string, 16).map_err(|_| Status::BadRequest)?;
+
+        // A chunk of zero size signals the end of the body
+        if size == 0 {
+            break;
+        }
+
+        // Stop before reading a chunk that would make the body too large
+        if size > max_size - body.len() {
+            return Err(Status::ContentTooLarge);
+        }
+
+        body.extend(read_bytes(stream, size).ok_or(Status::BadRequest)?);
+
+        // The chunk's data must be followed by a CRLF
+        if !read_line(stream).ok_or(Status::BadRequest)?.is_empty() {
+            return Err(Status::BadRequest);
+        }
+    }
+
+    // Then come the trailer fields (if any), up to the final empty line
+    let mut trailers = Headers::new();
+    let mut trailer_count = 0;
+    let mut trailer_size = 0;
+    loop {
+        let line = read_line(stream).ok_or(Status::BadRequest)?;
+
+        if line.is_empty() {
+            return Ok((body, trailers));
+        }
+
+        // Trailer fields are subject to the same limits as header fields
+        trailer_count += 1;
+        trailer_size += line.len();
+        if trailer_count > max_trailers || trailer_size > max_trailer_size {
+            return Err(Status::RequestHeaderFieldsTooLarge);
+        }
+
+        parse_header_line(&mut trailers, line).ok_or(Status::BadRequest)?;
+    }
+}
+
+/// Escape the characters of a string that have a special meaning in HTML
+pub fn html_escape(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+
+    for character in string.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            character => escaped.push(character),
+        }
+    }
+
+    escaped
+}
+
+/// Wrap a string in double quotes, escaping it as required for a JSON string (RFC 8259 §7)
+pub fn json_string(string: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for character in string.chars() {
+        match character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            character if character.is_control() => {
+                quoted.push_str(&format!("\\u{:04x}", character as u32))
+            }
+            character => quoted.push(character),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// Check whether a byte is an unreserved URI character (RFC 3986 §2.3)
+pub fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~')
+}
+
+/// Percent-encode every byte of a string for which the `allowed` function returns `false`
+pub fn percent_encode<F>(string: &str, allowed: F) -> String
+where
+    F: Fn(u8) -> bool,
+{
+    let mut encoded = String::new();
+
+    for byte in string.bytes() {
+        if allowed(byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+
+    encoded
+}
+
+/// Decode the percent-encoded bytes of a string, leaving malformed escapes as they are
+///
+/// The decoded bytes are interpreted as UTF-8, replacing any invalid sequences with `U+FFFD`
+pub fn percent_decode(string: &str) -> String {
+    let bytes = string.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| bytes.get(index + 1..index + 3))
+            .flatten()
+            .filter(|hex_digits| hex_digits.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex_digits| std::str::from_utf8(hex_digits).ok())
+            .and_then(|hex_digits| u8::from_str_radix(hex_digits, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Encode bytes in the standard base64 encoding of RFC 4648, with padding
+pub fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let buffer = group
+            .iter()
+            .enumerate()
+            .fold(0u32, |buffer, (index, &byte)| {
+                buffer | u32::from(byte) << (16 - 8 * index)
+            });
+
+        for index in 0..4 {
+            if index <= group.len() {
+                encoded.push(ALPHABET[(buffer >> (18 - 6 * index) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Decode a string in the standard base64 encoding of RFC 4648, with or without padding. Returns [`None`] if it isn't valid base64
+pub fn base64_decode(string: &str) -> Option<Vec<u8>> {
+    let string = string.trim_end_matches('=');
+    if string.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(string.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in string.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(decoded)
+}
+
+/// Parse a query string or an `application/x-www-form-urlencoded` body into name-value pairs, in the order they appear
+///
+/// Names and values are decoded individually, with a `+` standing for a space. A name without a value (like a flag) is given an empty value
+pub fn parse_urlencoded(string: &str) -> Vec<(String, String)> {
+    let decode = |component: &str| percent_decode(&component.replace('+', " "));
+
+    string
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(name), decode(value))
+        })
+        .collect()
+}
+
+/// Parse the value of a `Cookie` header into name-value pairs, in the order they appear
+///
+/// Pairs are separated by semicolons and surrounding whitespace is ignored. Values lose their surrounding double quotes (if any) and are percent-decoded, while pairs without a `=` aren't cookies, so they are skipped
+pub fn parse_cookies(header: &str) -> Vec<(String, String)> {
+    header
+        .split(';')
+        .filter_map(|pair| {
+            let (name, value) = pair.split_once('=')?;
+            let name = name.trim();
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+
+            (!name.is_empty()).then(|| (name.to_string(), percent_decode(value)))
+        })
+        .collect()
+}
+
+/// Compute the HMAC-SHA256 signature of a cookie's name and value with the provided key, as a lowercase hex string
+#[cfg(feature = "signed-cookies")]
+pub fn cookie_signature(name: &str, value: &str, key: &[u8]) -> String {
+    cookie_mac(name, value, key)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Check in constant time that a hex string is the HMAC-SHA256 signature of a cookie's name and value with the provided key
+#[cfg(feature = "signed-cookies")]
+pub fn verify_cookie_signature(name: &str, value: &str, signature: &str, key: &[u8]) -> bool {
+    if signature.len() != 64 || !signature.is_ascii() {
+        return false;
+    }
+
+    let signature: Option<Vec<u8>> = (0..signature.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&signature[index..index + 2], 16).ok())
+        .collect();
+
+    match signature {
+        Some(signature) => cookie_mac(name, value, key)
+            .verify_slice(&signature)
+            .is_ok(),
+        None => false,
+    }
+}
+
+/// Create an HMAC-SHA256 instance that has been fed a cookie's name and value
+#[cfg(feature = "signed-cookies")]
+fn cookie_mac(name: &str, value: &str, key: &[u8]) -> Hmac<Sha256> {
+    // HMAC accepts keys of any length, so this can't fail
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    // The name is signed as well, so that a signed value can't be moved to another cookie
+    mac.update(name.as_bytes());
+    mac.update(b"=");
+    mac.update(value.as_bytes());
+    mac
+}
+
+/// Returns the quality value that the `Accept` header of a request gives a media type, from `0.0` (not acceptable) to `1.0`
+///
+/// If several media ranges match, the most specific one applies (RFC 9110 §12.5.1). Without an `Accept` header, every media type is acceptable
+pub fn media_type_quality(headers: &Headers, media_type: &str) -> f32 {
+    let Some(accept) = get_header(headers, "Accept") else {
+        return 1.0;
+    };
+
+    // Parameters of the media type (such as `charset`) don't affect matching
+    let essence = media_type.split(';').next().unwrap_or_default().trim();
+    let (main_type, subtype) = essence.split_once('/').unwrap_or((essence, ""));
+
+    // The specificity and the quality value of the most specific media range that matches so far
+    let mut best: Option<(u8, f32)> = None;
+    for element in accept.split(',') {
+        let mut parameters = element.split(';');
+        let Some((range_type, range_subtype)) =
+            parameters.next().unwrap_or_default().trim().split_once('/')
+        else {
+            continue;
+        };
+
+        let specificity = if range_type == "*" && range_subtype == "*" {
+            0
+        } else if !range_type.eq_ignore_ascii_case(main_type) {
+            continue;
+        } else if range_subtype == "*" {
+            1
+        } else if range_subtype.eq_ignore_ascii_case(subtype) {
+            2
+        } else {
+            continue;
+        };
+
+        let quality = parameters
+            .find_map(|parameter| parameter.trim().strip_prefix("q="))
+            .map_or(Some(1.0), |quality| quality.trim().parse::<f32>().ok())
+            .filter(|quality| (0.0..=1.0).contains(quality));
+
+        if let Some(quality) = quality {
+            if best.is_none_or(|(best_specificity, _)| specificity > best_specificity) {
+                best = Some((specificity, quality));
+            }
+        }
+    }
+
+    best.map_or(0.0, |(_, quality)| quality)
+}
+
+/// Choose the content coding a response should be compressed with from the `Accept-Encoding` header of a request, preferring gzip over deflate
+#[cfg(feature = "compression")]
+pub fn negotiate_encoding(headers: &Headers) -> Option<&'static str> {
+    let accept_encoding = get_header(headers, "Accept-Encoding")?;
+
+    // Collect the quality value of each coding the client listed
+    let mut qualities = HashMap::new();
+    for element in accept_encoding.split(',') {
+        let mut parameters = element.split(';');
+        let coding = parameters
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let quality = parameters
+            .find_map(|parameter| parameter.trim().strip_prefix("q="))
+            .map_or(Some(1.0), |quality| quality.trim().parse::<f32>().ok());
+
+        if let Some(quality) = quality {
+            qualities.insert(coding, quality);
+        }
+    }
+
+    // A coding that isn't listed is only acceptable if the wildcard is
+    let quality = |coding: &str| {
+        qualities
+            .get(coding)
+            .or_else(|| qualities.get("*"))
+            .copied()
+            .unwrap_or(0.0)
+    };
+
+    let (gzip, deflate) = (quality("gzip"), quality("deflate"));
+    if gzip > 0.0 && gzip >= deflate {
+        Some("gzip")
+    } else if deflate > 0.0 {
+        Some("deflate")
+    } else {
+        None
+    }
+}
+
+/// Compress a message body with the given content coding, updating the headers accordingly
+///
+/// The body is left as is if it is empty or the headers already contain a `Content-Encoding` header
+#[cfg(feature = "compression")]
+pub fn compress_body(headers: &mut Headers, body: Vec<u8>, encoding: &str) -> Vec<u8> {
+    if body.is_empty() || get_header(headers, "Content-Encoding").is_some() {
+        return body;
+    }
+
+    let compressed = match encoding {
+        "gzip" => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&body).and_then(|_| encoder.finish())
+        }
+        "deflate" => {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&body).and_then(|_| encoder.finish())
+        }
+        _ => return body,
+    };
+
+    match compressed {
+        Ok(compressed) => {
+            headers.insert(String::from("Content-Encoding"), encoding.to_string());
+
+            // Caches must not serve the compressed body to clients that didn't ask for it
+            add_vary(headers, "Accept-Encoding");
+
+            compressed
+        }
+        Err(_) => body,
+    }
+}
+
+/// Returns the message a handler panicked with, if the payload of the panic is a string
+pub fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic payload"
+    }
+}
+
+/// The size of the buffer reads from a client are made through
+pub const READ_BUFFER_SIZE: usize = 8 * 1024;
+
+/// A wrapper around a [`ReadWrite`] stream that buffers reads, counts the bytes read from and written to it, and enforces a deadline on reading
+///
+/// Reading through the buffer means that parsing a request byte by byte doesn't result in a system call per byte
+pub struct CountingStream {
+    pub inner: Box<dyn ReadWrite>,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// The point in time after which reading fails, if any
+    pub deadline: Option<Instant>,
+    /// Whether a read has failed because the deadline passed
+    pub timed_out: bool,
+    buffer: Box<[u8]>,
+    position: usize,
+    filled: usize,
+}
+
+impl CountingStream {
+    pub fn new(inner: Box<dyn ReadWrite>) -> Self {
+        Self {
+            inner,
+            bytes_read: 0,
+            bytes_written: 0,
+            deadline: None,
+            timed_out: false,
+            buffer: vec![0; READ_BUFFER_SIZE].into_boxed_slice(),
+            position: 0,
+            filled: 0,
+        }
+    }
+
+    /// Make reading fail once the given amount of time has passed, or never if [`None`]
+    pub fn set_deadline(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.deadline = timeout.map(|timeout| Instant::now() + timeout);
+        self.timed_out = false;
+
+        if self.deadline.is_none() {
+            self.inner.set_read_timeout(None)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of bytes that have been received but not read yet
+    pub fn buffered(&self) -> usize {
+        self.filled - self.position
+    }
+
+    /// Read from the underlying stream, within the deadline
+    fn read_inner(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Only block for as long as the deadline allows
+        if let Some(deadline) = self.deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if remaining.is_zero() {
+                self.timed_out = true;
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+
+            self.inner.set_read_timeout(Some(remaining))?;
+        }
+
+        let read = match self.inner.read(buf) {
+            Ok(read) => read,
+            Err(error) => {
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) {
+                    self.timed_out = true;
+                }
+                return Err(error);
+            }
+        };
+
+        self.bytes_read += read as u64;
+        Ok(read)
+    }
+}
+
+impl Read for CountingStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Large reads (such as message bodies) gain nothing from the buffer, so they bypass it once it is empty
+        if self.buffered() == 0 && buf.len() >= self.buffer.len() {
+            return self.read_inner(buf);
+        }
+
+        let available = self.fill_buf()?;
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.consume(read);
+
+        Ok(read)
+    }
+}
+
+impl BufRead for CountingStream {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.buffered() == 0 {
+            let mut buffer = std::mem::take(&mut self.buffer);
+            let read = self.read_inner(&mut buffer);
+            self.buffer = buffer;
+
+            // Nothing is buffered after a failed read, so that retrying it doesn't return the previous contents again
+            self.position = 0;
+            self.filled = *read.as_ref().unwrap_or(&0);
+            read?;
+        }
+
+        Ok(&self.buffer[self.position..self.filled])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.filled);
+    }
+}
+
+impl Write for CountingStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes_written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// A stream that returns the results of a script of reads, one per call, and discards everything written to it
+    struct ScriptedStream {
+        reads: VecDeque<io::Result<Vec<u8>>>,
+    }
+
+    impl ScriptedStream {
+        fn new(reads: Vec<io::Result<&[u8]>>) -> Self {
+            Self {
+                reads: reads
+                    .into_iter()
+                    .map(|read| read.map(<[u8]>::to_vec))
+                    .collect(),
+            }
+        }
+    }
+
+    impl Read for ScriptedStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.reads.pop_front() {
+                Some(Ok(mut data)) => {
+                    // Keep whatever doesn't fit for the next read
+                    let read = data.len().min(buf.len());
+                    buf[..read].copy_from_slice(&data[..read]);
+                    if read < data.len() {
+                        self.reads.push_front(Ok(data.split_off(read)));
+                    }
+                    Ok(read)
+                }
+                Some(Err(error)) => Err(error),
+                None => Ok(0),
+            }
+        }
+    }
+
+    impl Write for ScriptedStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl ReadWrite for ScriptedStream {}
+
+    fn counting_stream(reads: Vec<io::Result<&[u8]>>) -> CountingStream {
+        CountingStream::new(Box::new(ScriptedStream::new(reads)))
+    }
+
+    #[test]
+    fn interrupted_read_is_retried_without_replaying_the_buffer() {
+        let mut stream = counting_stream(vec![
+            Ok(b"GET /a HTTP/1.1\r\nHo"),
+            Err(io::ErrorKind::Interrupted.into()),
+            Ok(b"st: x\r\n\r\n"),
+        ]);
+
+        assert_eq!(read_line(&mut stream).as_deref(), Some("GET /a HTTP/1.1"));
+        assert_eq!(read_line(&mut stream).as_deref(), Some("Host: x"));
+        assert_eq!(read_line(&mut stream).as_deref(), Some(""));
+        assert_eq!(stream.bytes_read, 28);
+    }
+
+    #[test]
+    fn short_reads_are_joined_into_lines() {
+        let mut stream = counting_stream(vec![
+            Ok(b"GE"),
+            Ok(b"T / H"),
+            Ok(b"TTP/1.1\r"),
+            Ok(b"\n"),
+            Ok(b"\r\n"),
+        ]);
+
+        assert_eq!(read_line(&mut stream).as_deref(), Some("GET / HTTP/1.1"));
+        assert_eq!(read_line(&mut stream).as_deref(), Some(""));
+        assert_eq!(read_line(&mut stream), None);
+    }
+
+    #[test]
+    fn would_block_is_reported_as_a_timeout() {
+        let mut stream = counting_stream(vec![
+            Ok(b"GET / HTTP/1.1\r\n"),
+            Err(io::ErrorKind::WouldBlock.into()),
+            Ok(b"Host: x\r\n"),
+        ]);
+
+        assert_eq!(read_line(&mut stream).as_deref(), Some("GET / HTTP/1.1"));
+        assert!(!stream.timed_out);
+        assert_eq!(read_line(&mut stream), None);
+        assert!(stream.timed_out);
+
+        // Reading again after the deadline has been reset doesn't return anything read before
+        stream.set_deadline(None).unwrap();
+        assert!(!stream.timed_out);
+        assert_eq!(read_line(&mut stream).as_deref(), Some("Host: x"));
+    }
+
+    #[test]
+    fn large_reads_bypass_the_buffer_once_it_is_empty() {
+        let body: Vec<u8> = (0..30_000).map(|index| (index % 251) as u8).collect();
+        let mut first = b"POST / HTTP/1.1\r\n".to_vec();
+        first.extend(&body[..5_000]);
+        let mut stream = counting_stream(vec![Ok(&first), Ok(&body[5_000..])]);
+
+        assert_eq!(read_line(&mut stream).as_deref(), Some("POST / HTTP/1.1"));
+        assert_eq!(stream.buffered(), 5_000);
+
+        // The buffered part of the body comes first, then the rest is read directly
+        let mut buf = vec![0; READ_BUFFER_SIZE * 2];
+        assert_eq!(stream.read(&mut buf).unwrap(), 5_000);
+        assert_eq!(buf[..5_000], body[..5_000]);
+        assert_eq!(stream.read(&mut buf).unwrap(), READ_BUFFER_SIZE * 2);
+        assert_eq!(buf[..], body[5_000..5_000 + READ_BUFFER_SIZE * 2]);
+        assert_eq!(stream.buffered(), 0);
+
+        let rest = read_bytes(&mut stream, body.len() - 5_000 - READ_BUFFER_SIZE * 2).unwrap();
+        assert_eq!(rest, body[5_000 + READ_BUFFER_SIZE * 2..]);
+        assert_eq!(stream.bytes_read, first.len() as u64 + 25_000);
+    }
+
+    #[test]
+    fn read_line_rejects_incomplete_and_overly_long_lines() {
+        let mut stream = ScriptedStream::new(vec![Ok(b"GET / HTTP/1.1")]);
+        assert_eq!(read_line(&mut stream), None);
+
+        let long_line = vec![b'a'; MAX_LINE_LENGTH + 1];
+        let mut stream = ScriptedStream::new(vec![Ok(&long_line), Ok(b"\r\n")]);
+        assert_eq!(read_line(&mut stream), None);
+
+        // Only a CRLF ends a line
+        let mut stream = ScriptedStream::new(vec![Ok(b"a\nb\r\n")]);
+        assert_eq!(read_line(&mut stream).as_deref(), Some("a\nb"));
+    }
+
+    #[test]
+    fn chunked_body_is_read_across_short_and_interrupted_reads() {
+        let mut stream = counting_stream(vec![
+            Ok(b"5;name=value\r\nhel"),
+            Err(io::ErrorKind::Interrupted.into()),
+            Ok(b"lo\r\n6\r\n wo"),
+            Ok(b"rld\r\n0\r\nDigest: x\r\n"),
+            Ok(b"\r\nGET /next HTTP/1.1\r\n"),
+        ]);
+
+        let (body, trailers) = read_chunked_body(&mut stream, 100, 10, 100).unwrap();
+        assert_eq!(body, b"hello world");
+        assert_eq!(trailers.get("Digest").map(String::as_str), Some("x"));
+
+        // The next request is left in the stream
+        assert_eq!(
+            read_line(&mut stream).as_deref(),
+            Some("GET /next HTTP/1.1")
+        );
+    }
+
+    #[test]
+    fn malformed_chunked_bodies_are_rejected() {
+        let read = |input: &[u8], max_size: usize, max_trailers: usize| {
+            read_chunked_body(
+                &mut ScriptedStream::new(vec![Ok(input)]),
+                max_size,
+                max_trailers,
+                100,
+            )
+        };
+
+        assert_eq!(
+            read(b"zz\r\nhello\r\n0\r\n\r\n", 100, 10),
+            Err(Status::BadRequest)
+        );
+        assert_eq!(
+            read(b"+5\r\nhello\r\n0\r\n\r\n", 100, 10),
+            Err(Status::BadRequest)
+        );
+        assert_eq!(
+            read(b"5\r\nhelloX\r\n0\r\n\r\n", 100, 10),
+            Err(Status::BadRequest)
+        );
+        assert_eq!(read(b"5\r\nhel", 100, 10), Err(Status::BadRequest));
+        assert_eq!(
+            read(b"5\r\nhello\r\n0\r\n", 100, 10),
+            Err(Status::BadRequest)
+        );
+        assert_eq!(
+            read(b"5\r\nhello\r\n0\r\nnot a field\r\n\r\n", 100, 10),
+            Err(Status::BadRequest)
+        );
+        assert_eq!(
+            read(b"5\r\nhello\r\n0\r\n\r\n", 4, 10),
+            Err(Status::ContentTooLarge)
+        );
+        assert_eq!(
+            read(b"ffffffffffffffff\r\n", 100, 10),
+            Err(Status::ContentTooLarge)
+        );
+        assert_eq!(
+            read(b"0\r\nA: 1\r\nB: 2\r\n\r\n", 100, 1),
+            Err(Status::RequestHeaderFieldsTooLarge)
+        );
+    }
+
+    #[test]
+    fn http_dates_are_validated() {
+        let at = |seconds| Some(UNIX_EPOCH + Duration::from_secs(seconds));
+
+        // Leap days only exist in leap years
+        assert_eq!(
+            parse_http_date("Tue, 29 Feb 2000 00:00:00 GMT"),
+            at(951782400)
+        );
+        assert_eq!(parse_http_date("Sun, 29 Feb 2100 00:00:00 GMT"), None);
+        assert_eq!(parse_http_date("Tue Feb 29 00:00:00 2000"), at(951782400));
+
+        // A leap second is the same as the first second of the next minute
+        assert_eq!(
+            parse_http_date("Sat, 31 Dec 2016 23:59:60 GMT"),
+            at(1483228800)
+        );
+        assert_eq!(parse_http_date("Sat, 31 Dec 2016 24:00:00 GMT"), None);
+
+        assert_eq!(parse_http_date("Wed, 31 Dec 1969 23:59:59 GMT"), None);
+        assert_eq!(parse_http_date("Tue, 29 Feb 2000 00:00:00 UTC"), None);
+        assert_eq!(parse_http_date("Tue, 29 Feb 2000 00:00:00 GMT extra"), None);
+        assert_eq!(parse_http_date("Tuesday, 29-Feb-2000 00:00:00 GMT"), None);
+        assert_eq!(parse_http_date(""), None);
+
+        assert_eq!(
+            parse_http_date("Tue, 29 Feb 2000 00:00:00 GMT")
+                .map(format_time)
+                .as_deref(),
+            Some("Tue, 29 Feb 2000 00:00:00 GMT")
+        );
     }
 }
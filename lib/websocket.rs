@@ -0,0 +1,319 @@
+//! WebSocket connections (RFC 6455), upgraded from HTTP requests with [`Response::upgrade_websocket()`](crate::Response::upgrade_websocket) (requires the `websocket` feature)
+//!
+//! Once a request has been upgraded, the handler exchanges [`Message`]s with the client through a [`WebSocket`] until either side closes it.
+//! Only the base protocol is supported, without any extensions (such as compression) or subprotocols
+//!
+//! # Example
+//!
+//! ```
+//! use oak_http_server::Server;
+//!
+//! fn main() {
+//!     let mut server = Server::new("localhost", 2300 as u16);
+//!
+//!     // Send every message back to the client
+//!     server.on_get("/echo", |request, response| {
+//!         if let Some(mut socket) = response.upgrade_websocket(&request) {
+//!             while let Ok(Some(message)) = socket.recv() {
+//!                 if socket.send(message).is_err() {
+//!                     break;
+//!                 }
+//!             }
+//!         }
+//!     });
+//! }
+//! ```
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use sha1::{Digest, Sha1};
+
+use crate::utils::*;
+use crate::{Connection, Method, Request};
+
+/// The GUID that is appended to the key of the client when computing the `Sec-WebSocket-Accept` header (RFC 6455 §1.3)
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+const CLOSE_NORMAL: u16 = 1000;
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_INVALID_DATA: u16 = 1007;
+const CLOSE_TOO_BIG: u16 = 1009;
+
+/// A message exchanged over a [`WebSocket`]
+#[derive(PartialEq, Clone, Debug)]
+pub enum Message {
+    /// A message containing UTF-8 text
+    Text(String),
+    /// A message containing binary data
+    Binary(Vec<u8>),
+}
+
+/// A WebSocket connection with a client, created by [`Response::upgrade_websocket()`](crate::Response::upgrade_websocket)
+///
+/// Messages larger than the maximum body size of the server (see [`Server::max_body_size()`](crate::Server::max_body_size)) are rejected. The connection is closed when the [`WebSocket`] is dropped
+pub struct WebSocket<'s> {
+    parent: &'s mut Connection,
+    closed: bool,
+}
+
+impl<'s> WebSocket<'s> {
+    pub(crate) fn new(parent: &'s mut Connection) -> Self {
+        Self {
+            parent,
+            closed: false,
+        }
+    }
+
+    /// Send a message to the client
+    ///
+    /// Returns an error if the connection has been closed or the client has disconnected
+    pub fn send(&mut self, message: Message) -> io::Result<()> {
+        if self.closed {
+            return Err(io::ErrorKind::NotConnected.into());
+        }
+
+        match message {
+            Message::Text(text) => self.write_frame(OPCODE_TEXT, text.as_bytes()),
+            Message::Binary(data) => self.write_frame(OPCODE_BINARY, &data),
+        }
+    }
+
+    /// Wait for the next message from the client, answering any pings in the meantime
+    ///
+    /// Returns [`None`] once the connection has been closed. If the client violates the protocol, the connection is closed and an error is returned
+    pub fn recv(&mut self) -> io::Result<Option<Message>> {
+        // The opcode and the payload received so far of a fragmented message
+        let mut message: Option<(u8, Vec<u8>)> = None;
+
+        while !self.closed {
+            let Frame {
+                fin,
+                opcode,
+                payload,
+            } = match read_frame(&mut self.parent.stream, self.parent.max_body_size) {
+                Ok(frame) => frame,
+                Err(FrameError::Io(error)) => {
+                    self.closed = true;
+                    return Err(error);
+                }
+                Err(FrameError::Protocol(code, reason)) => return Err(self.fail(code, reason)),
+            };
+
+            match opcode {
+                OPCODE_PING => self.write_frame(OPCODE_PONG, &payload)?,
+                OPCODE_PONG => (),
+                OPCODE_CLOSE => {
+                    // Echo the status code of the client (if any) to complete the closing handshake
+                    let _ = self.write_frame(OPCODE_CLOSE, payload.get(..2).unwrap_or_default());
+                    self.closed = true;
+                }
+                OPCODE_TEXT | OPCODE_BINARY if message.is_none() => {
+                    message = Some((opcode, payload));
+                }
+                OPCODE_CONTINUATION if message.is_some() => {
+                    let (_, received) = message.as_mut().unwrap();
+                    if received.len() + payload.len() > self.parent.max_body_size {
+                        return Err(self.fail(CLOSE_TOO_BIG, "message too large"));
+                    }
+                    received.extend(payload);
+                }
+                _ => return Err(self.fail(CLOSE_PROTOCOL_ERROR, "unexpected frame")),
+            }
+
+            // Data frames are complete once the final fragment has arrived
+            if fin && opcode & 0x8 == 0 {
+                return match message.take() {
+                    Some((OPCODE_TEXT, payload)) => match String::from_utf8(payload) {
+                        Ok(text) => Ok(Some(Message::Text(text))),
+                        Err(_) => Err(self.fail(CLOSE_INVALID_DATA, "text message isn't UTF-8")),
+                    },
+                    Some((_, payload)) => Ok(Some(Message::Binary(payload))),
+                    None => Ok(None),
+                };
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Close the connection with a `1000 Normal Closure` status code
+    ///
+    /// This is the same as dropping the [`WebSocket`], except that it reports whether the client could be notified
+    pub fn close(mut self) -> io::Result<()> {
+        self.closed = true;
+        self.write_frame(OPCODE_CLOSE, &CLOSE_NORMAL.to_be_bytes())
+    }
+
+    /// Close the connection with the given status code after a protocol violation by the client, returning an error describing it
+    fn fail(&mut self, code: u16, reason: &str) -> io::Error {
+        let _ = self.write_frame(OPCODE_CLOSE, &code.to_be_bytes());
+        self.closed = true;
+
+        io::Error::new(io::ErrorKind::InvalidData, reason)
+    }
+
+    /// Send a single unfragmented frame. Frames sent by the server aren't masked
+    fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mut head = vec![0x80 | opcode];
+        match payload.len() {
+            length @ 0..=125 => head.push(length as u8),
+            length @ 126..=0xFFFF => {
+                head.push(126);
+                head.extend((length as u16).to_be_bytes());
+            }
+            length => {
+                head.push(127);
+                head.extend((length as u64).to_be_bytes());
+            }
+        }
+
+        self.parent.stream.write_all(&head)?;
+        self.parent.stream.write_all(payload)?;
+        self.parent.stream.flush()
+    }
+}
+
+impl Drop for WebSocket<'_> {
+    fn drop(&mut self) {
+        // If the client has disconnected, there is no one left to notify
+        if !self.closed {
+            let _ = self.write_frame(OPCODE_CLOSE, &CLOSE_NORMAL.to_be_bytes());
+        }
+    }
+}
+
+impl fmt::Debug for WebSocket<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WebSocket")
+            .field("connection", &self.parent)
+            .field("closed", &self.closed)
+            .finish()
+    }
+}
+
+/// A frame received from the client, with its payload unmasked
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+enum FrameError {
+    /// The frame couldn't be read from the client
+    Io(io::Error),
+    /// The frame violates the protocol, so the connection should be closed with the given status code
+    Protocol(u16, &'static str),
+}
+
+impl From<io::Error> for FrameError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Read a frame sent by the client, rejecting it if its payload is larger than `max_size` bytes
+fn read_frame<R: Read>(stream: &mut R, max_size: usize) -> Result<Frame, FrameError> {
+    let mut head = [0; 2];
+    stream.read_exact(&mut head)?;
+
+    let fin = head[0] & 0x80 != 0;
+    let opcode = head[0] & 0x0F;
+    // The reserved bits are only used by extensions, none of which are negotiated
+    if head[0] & 0x70 != 0 {
+        return Err(FrameError::Protocol(
+            CLOSE_PROTOCOL_ERROR,
+            "reserved bits set",
+        ));
+    }
+    // Frames sent by clients must be masked (RFC 6455 §5.1)
+    if head[1] & 0x80 == 0 {
+        return Err(FrameError::Protocol(CLOSE_PROTOCOL_ERROR, "unmasked frame"));
+    }
+
+    let length = match head[1] & 0x7F {
+        126 => {
+            let mut length = [0; 2];
+            stream.read_exact(&mut length)?;
+            u64::from(u16::from_be_bytes(length))
+        }
+        127 => {
+            let mut length = [0; 8];
+            stream.read_exact(&mut length)?;
+            u64::from_be_bytes(length)
+        }
+        length => u64::from(length),
+    };
+
+    // Control frames can't be fragmented and their payload is at most 125 bytes long
+    if opcode & 0x8 != 0 && (!fin || length > 125) {
+        return Err(FrameError::Protocol(
+            CLOSE_PROTOCOL_ERROR,
+            "invalid control frame",
+        ));
+    }
+    if length > max_size as u64 {
+        return Err(FrameError::Protocol(CLOSE_TOO_BIG, "message too large"));
+    }
+
+    let mut mask = [0; 4];
+    stream.read_exact(&mut mask)?;
+    let mut payload = vec![0; length as usize];
+    stream.read_exact(&mut payload)?;
+    for (index, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[index % 4];
+    }
+
+    Ok(Frame {
+        fin,
+        opcode,
+        payload,
+    })
+}
+
+/// Check that a request is a valid WebSocket opening handshake (RFC 6455 §4.2.1), returning its `Sec-WebSocket-Key` header or the reason it was rejected
+pub(crate) fn handshake_key(request: &Request) -> Result<&str, &'static str> {
+    if request.method != Method::GET || (request.version.major, request.version.minor) < (1, 1) {
+        return Err("the request must be a GET request of HTTP/1.1 or later");
+    }
+    if !request
+        .header("Upgrade")
+        .is_some_and(|upgrade| has_token(upgrade, "websocket"))
+    {
+        return Err("expected 'Upgrade: websocket' header");
+    }
+    if !request
+        .header("Connection")
+        .is_some_and(|connection| has_token(connection, "Upgrade"))
+    {
+        return Err("expected 'Connection: Upgrade' header");
+    }
+    if request
+        .header("Sec-WebSocket-Version")
+        .is_none_or(|version| version.trim() != "13")
+    {
+        return Err("unsupported WebSocket version");
+    }
+
+    // The key is a random 16-byte value encoded in base64
+    match request.header("Sec-WebSocket-Key") {
+        Some(key) if base64_decode(key.trim()).is_some_and(|key| key.len() == 16) => Ok(key.trim()),
+        _ => Err("invalid 'Sec-WebSocket-Key' header"),
+    }
+}
+
+/// Compute the value of the `Sec-WebSocket-Accept` header that answers a `Sec-WebSocket-Key`
+pub(crate) fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.as_bytes());
+    hasher.update(HANDSHAKE_GUID.as_bytes());
+
+    base64_encode(&hasher.finalize())
+}
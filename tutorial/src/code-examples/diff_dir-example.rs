@@ -7,8 +7,8 @@ fn main() {
 
     let mut server = Server::new(hostname, port);
 
-    // The handler will serve static files for the '/www' path from the local '/diff' path
-    server.on_directory("/www", read_diff_dir("/diff"));
+    // The handler will serve static files for the '/www' path from the local 'diff' directory
+    server.on_directory("/www", read_diff_dir("diff"));
 
     server.start(|| {
         println!("HTTP server is now running...");
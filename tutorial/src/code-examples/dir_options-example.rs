@@ -0,0 +1,27 @@
+extern crate oak_http_server;
+use oak_http_server::{
+    handlers::{read_diff_dir_with_options, DirOptions},
+    Server,
+};
+
+fn main() {
+    let hostname = "localhost";
+    let port: u16 = 2300;
+
+    let mut server = Server::new(hostname, port);
+
+    server.on_directory(
+        "/files",
+        read_diff_dir_with_options(
+            "public",
+            DirOptions {
+                index: Some(String::from("default.html")),
+                listing: true,
+            },
+        ),
+    );
+
+    server.start(|| {
+        println!("Successfully initiated server");
+    });
+}
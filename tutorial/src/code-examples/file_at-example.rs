@@ -0,0 +1,16 @@
+extern crate oak_http_server;
+use oak_http_server::{handlers::read_file_at, Server};
+
+fn main() {
+    let hostname = "localhost";
+    let port: u16 = 2300;
+
+    let mut server = Server::new(hostname, port);
+
+    // The handler will serve the local 'static/robots.txt' file for the '/robots.txt' path
+    server.on_get("/robots.txt", read_file_at("static/robots.txt"));
+
+    server.start(|| {
+        println!("HTTP server is now running...");
+    });
+}
@@ -0,0 +1,25 @@
+extern crate oak_http_server;
+use oak_http_server::Server;
+
+fn main() {
+    let hostname = "localhost";
+    let port: u16 = 2300;
+
+    // Create a Server instance
+    let mut server = Server::new(hostname, port);
+
+    // Respond with the ID of the requested user (e.g. "42" for "/users/42")
+    server.on_get("/users/:id", |request, response| {
+        response.send(format!("Requested user {}", request.params["id"]));
+    });
+
+    // Exact routes are preferred over parameterized ones, so "/users/me" is always processed here
+    server.on_get("/users/me", |_request, response| {
+        response.send("Requested the current user");
+    });
+
+    // Start the HTTP server
+    server.start(|| {
+        println!("HTTP server is now running...");
+    });
+}